@@ -13,7 +13,7 @@
         naersk-lib = naersk.lib."${system}";
 
         nativeBuildInputs = with pkgs; [ pkgconfig python3 ];
-        buildInputs = with pkgs; [ libpulseaudio xorg.libxcb xorg.libXScrnSaver x11 ];
+        buildInputs = with pkgs; [ dbus libpulseaudio xorg.libxcb xorg.libXScrnSaver x11 ];
       in
       rec {
         # `nix build`
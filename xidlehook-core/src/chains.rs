@@ -0,0 +1,200 @@
+//! Several independent chains of timers, evaluated against the same
+//! idle time. Each chain is a whole `Xidlehook` instance with its own
+//! position, modules and abort state, so that for example "dim after
+//! 60s, lock after 70s" and an unrelated "pause the music after 10
+//! minutes" don't have to be interleaved into one chain.
+
+use crate::{Action, Drive, IdleSource, Module, Result, Timer, Xidlehook};
+
+use std::{cmp, fmt, time::Duration};
+
+/// A list of named chains, which are polled in the order they were
+/// added
+pub struct Chains<T: Timer, M: Module> {
+    chains: Vec<(String, Xidlehook<T, M>)>,
+
+    /// The idle source's file descriptors, for `main_async`
+    #[cfg(feature = "tokio")]
+    wakers: crate::Wakers,
+}
+impl<T: Timer, M: Module> Chains<T, M> {
+    /// An empty instance without any chains
+    pub fn new() -> Self {
+        Self {
+            chains: Vec::new(),
+
+            #[cfg(feature = "tokio")]
+            wakers: crate::Wakers::default(),
+        }
+    }
+
+    /// Return this instance with an additional chain. See `insert`.
+    pub fn with_chain<S: Into<String>>(mut self, name: S, chain: Xidlehook<T, M>) -> Self {
+        self.insert(name, chain);
+        self
+    }
+
+    /// Add a chain, or replace the one with the same name. Returns
+    /// the replaced chain, which is not aborted.
+    pub fn insert<S: Into<String>>(
+        &mut self,
+        name: S,
+        chain: Xidlehook<T, M>,
+    ) -> Option<Xidlehook<T, M>> {
+        let name = name.into();
+        match self.get_mut(&name) {
+            Some(existing) => Some(std::mem::replace(existing, chain)),
+            None => {
+                self.chains.push((name, chain));
+                None
+            },
+        }
+    }
+
+    /// Remove a chain, without aborting it
+    pub fn remove(&mut self, name: &str) -> Option<Xidlehook<T, M>> {
+        let index = self.chains.iter().position(|(other, _)| other == name)?;
+        Some(self.chains.remove(index).1)
+    }
+
+    /// Returns the chain with this name, if any
+    pub fn get(&self, name: &str) -> Option<&Xidlehook<T, M>> {
+        self.chains
+            .iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, chain)| chain)
+    }
+
+    /// Returns the chain with this name mutably, if any
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Xidlehook<T, M>> {
+        self.chains
+            .iter_mut()
+            .find(|(other, _)| other == name)
+            .map(|(_, chain)| chain)
+    }
+
+    /// Returns the names of all chains
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.chains.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Returns all chains along with their names
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Xidlehook<T, M>)> {
+        self.chains
+            .iter()
+            .map(|(name, chain)| (name.as_str(), chain))
+    }
+
+    /// Returns all chains mutably along with their names
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut Xidlehook<T, M>)> {
+        self.chains
+            .iter_mut()
+            .map(|(name, chain)| (name.as_str(), chain))
+    }
+
+    /// Returns the number of chains
+    pub fn len(&self) -> usize {
+        self.chains.len()
+    }
+
+    /// Returns true if there are no chains
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+
+    /// Abort every chain, see `Xidlehook::abort`
+    pub fn abort(&mut self) -> Result<()> {
+        for (_, chain) in &mut self.chains {
+            chain.abort()?;
+        }
+        Ok(())
+    }
+
+    /// Restart every chain from index zero, see `Xidlehook::reset`
+    pub fn reset(&mut self, absolute_time: Duration) -> Result<()> {
+        for (_, chain) in &mut self.chains {
+            chain.reset(absolute_time)?;
+        }
+        Ok(())
+    }
+
+    /// Polls every chain, see `Xidlehook::poll`. Returns the shortest
+    /// time any chain wants to sleep for, `Action::Forever` if none of
+    /// them has anything left to do, or `Action::Quit` as soon as one
+    /// of them wants to quit.
+    pub fn poll(&mut self, absolute_time: Duration) -> Result<Action> {
+        let mut max_sleep = None;
+        for (_, chain) in &mut self.chains {
+            match chain.poll(absolute_time)? {
+                Action::Sleep(delay) => {
+                    max_sleep = Some(max_sleep.map_or(delay, |max| cmp::min(max, delay)));
+                },
+                Action::Forever => (),
+                Action::Quit => return Ok(Action::Quit),
+            }
+        }
+        Ok(max_sleep.map_or(Action::Forever, Action::Sleep))
+    }
+
+    /// Runs a standard poll-sleep-repeat loop over all chains, see
+    /// `Xidlehook::main_sync`
+    pub fn main_sync<S, F>(mut self, source: &S, callback: F) -> Result<()>
+    where
+        S: IdleSource + ?Sized,
+        F: FnMut() -> bool,
+    {
+        crate::run_sync(&mut self, source, callback)
+    }
+
+    /// Runs a standard poll-sleep-repeat loop over all chains...
+    /// asynchronously.
+    #[cfg(any(feature = "async-std", feature = "tokio"))]
+    pub async fn main_async<S>(&mut self, source: &S) -> Result<()>
+    where
+        S: IdleSource + ?Sized,
+    {
+        crate::run_async(self, source).await
+    }
+}
+impl<T: Timer, M: Module> Default for Chains<T, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T: Timer, M: Module> Drive for Chains<T, M> {
+    fn poll(&mut self, absolute_time: Duration) -> Result<Action> {
+        Chains::poll(self, absolute_time)
+    }
+    fn set_activity_wakeups(&mut self, value: bool) {
+        for (_, chain) in &mut self.chains {
+            chain.set_activity_wakeups(value);
+        }
+    }
+    fn wants_activity(&self) -> bool {
+        self.chains.iter().any(|(_, chain)| chain.next_index() > 0)
+    }
+    fn detect_sleep(&self) -> bool {
+        self.chains.iter().any(|(_, chain)| chain.detect_sleep())
+    }
+    fn woke_up(&mut self, absolute_time: Duration) -> Result<()> {
+        for (_, chain) in &mut self.chains {
+            if chain.detect_sleep() {
+                chain.reset(absolute_time)?;
+            }
+        }
+        Ok(())
+    }
+    #[cfg(feature = "tokio")]
+    fn wakers(&mut self) -> &mut crate::Wakers {
+        &mut self.wakers
+    }
+}
+impl<T, M> fmt::Debug for Chains<T, M>
+where
+    T: Timer,
+    M: Module + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,103 @@
+//! Human-friendly durations, such as `500ms`, `90s`, `5m` or `1h30m`.
+//! For backwards compatibility, a bare number is a number of seconds.
+
+use crate::Result;
+
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt::Write,
+    time::Duration,
+};
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// All units, from smallest to largest, with their length in
+/// nanoseconds
+const UNITS: &[(&str, u128)] = &[
+    ("ns", 1),
+    ("us", 1000),
+    ("ms", 1000 * 1000),
+    ("s", NANOS_PER_SEC),
+    ("m", 60 * NANOS_PER_SEC),
+    ("h", 60 * 60 * NANOS_PER_SEC),
+    ("d", 24 * 60 * 60 * NANOS_PER_SEC),
+];
+
+/// Parse a duration made up of one or more numbers followed by a unit
+/// (`ns`, `us`, `ms`, `s`, `m`, `h` or `d`), which are added together
+// The remainder is kept separately
+#[allow(clippy::integer_division)]
+pub fn parse(input: &str) -> Result<Duration> {
+    let input = input.trim();
+    if let Ok(secs) = input.parse() {
+        return Ok(Duration::from_secs(secs));
+    }
+    if input.is_empty() {
+        return Err("empty duration".into());
+    }
+
+    let too_large = || format!("invalid duration {:?}: too large", input);
+
+    let mut total: u128 = 0;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(digits);
+        let letters = tail
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(letters);
+
+        if number.is_empty() {
+            return Err(format!("invalid duration {:?}: expected a number", input).into());
+        }
+        if unit.is_empty() {
+            return Err(format!(
+                "invalid duration {:?}: missing unit after {}",
+                input, number
+            )
+            .into());
+        }
+        let unit = UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .map(|&(_, nanos)| nanos)
+            .ok_or_else(|| format!("invalid duration {:?}: unknown unit {:?}", input, unit))?;
+
+        total = number
+            .parse::<u128>()
+            .ok()
+            .and_then(|number| number.checked_mul(unit))
+            .and_then(|part| total.checked_add(part))
+            .ok_or_else(too_large)?;
+
+        rest = tail.trim_start();
+    }
+    let secs = u64::try_from(total / NANOS_PER_SEC).map_err(|_| too_large())?;
+    let nanos = (total % NANOS_PER_SEC)
+        .try_into()
+        .expect("less than a second fits in u32");
+    Ok(Duration::new(secs, nanos))
+}
+
+/// Format a duration the way `parse` reads it, such as `1h30m`, down
+/// to the nanosecond, so that it reads back exactly the same
+// The divisions are meant to round down
+#[allow(clippy::integer_division)]
+pub fn format(duration: Duration) -> String {
+    let mut nanos = duration.as_nanos();
+    if nanos == 0 {
+        return String::from("0s");
+    }
+
+    let mut output = String::new();
+    for &(name, unit) in UNITS.iter().rev() {
+        if nanos >= unit {
+            let _ = write!(output, "{}{}", nanos / unit, name);
+            nanos %= unit;
+        }
+    }
+    output
+}
@@ -0,0 +1,148 @@
+//! The idle source trait and some useful implementations such as
+//! combinators
+
+use crate::Result;
+
+use std::{os::unix::io::RawFd, rc::Rc, time::Duration};
+
+/// An idle source is what tells xidlehook how long the user has been
+/// idle. The `Xcb` module is the default one, but anything that can
+/// measure the time since the last user input works.
+pub trait IdleSource {
+    /// Return the absolute time since the user last gave any input
+    fn get_idle(&self) -> Result<Duration>;
+
+    /// Prepare to wake up the main loop once the idle time reaches
+    /// `idle + delay`, and if `activity` is set, as soon as the user
+    /// becomes active. It's only set while a timer is active, since
+    /// waking up on every key press is worse than polling otherwise.
+    /// Returns the file descriptors of which one becomes readable when
+    /// either happens, or none if this source can't do that, in which
+    /// case the main loop simply sleeps for `delay`. A source should
+    /// return the same file descriptors every time and keep them open
+    /// for as long as it lives, since the async main loop registers
+    /// them only once.
+    fn arm(&self, _idle: Duration, _delay: Duration, _activity: bool) -> Result<Vec<RawFd>> {
+        Ok(Vec::new())
+    }
+    /// Called after the main loop woke up from waiting on the file
+    /// descriptors returned by `arm`, or stopped waiting because it
+    /// was cancelled. Consume any pending events here.
+    fn disarm(&self) -> Result<()> {
+        Ok(())
+    }
+    /// Return true if `arm` wakes the main loop as soon as the user
+    /// becomes active, when asked to. Timers then don't need to be polled early
+    /// for their abort commands (see `Timer::abort_urgency`).
+    fn wakes_on_activity(&self) -> bool {
+        false
+    }
+}
+
+impl<S: IdleSource + ?Sized> IdleSource for &S {
+    fn get_idle(&self) -> Result<Duration> {
+        (**self).get_idle()
+    }
+    fn arm(&self, idle: Duration, delay: Duration, activity: bool) -> Result<Vec<RawFd>> {
+        (**self).arm(idle, delay, activity)
+    }
+    fn disarm(&self) -> Result<()> {
+        (**self).disarm()
+    }
+    fn wakes_on_activity(&self) -> bool {
+        (**self).wakes_on_activity()
+    }
+}
+
+impl<S: IdleSource + ?Sized> IdleSource for Box<S> {
+    fn get_idle(&self) -> Result<Duration> {
+        (**self).get_idle()
+    }
+    fn arm(&self, idle: Duration, delay: Duration, activity: bool) -> Result<Vec<RawFd>> {
+        (**self).arm(idle, delay, activity)
+    }
+    fn disarm(&self) -> Result<()> {
+        (**self).disarm()
+    }
+    fn wakes_on_activity(&self) -> bool {
+        (**self).wakes_on_activity()
+    }
+}
+
+impl<S: IdleSource + ?Sized> IdleSource for Rc<S> {
+    fn get_idle(&self) -> Result<Duration> {
+        (**self).get_idle()
+    }
+    fn arm(&self, idle: Duration, delay: Duration, activity: bool) -> Result<Vec<RawFd>> {
+        (**self).arm(idle, delay, activity)
+    }
+    fn disarm(&self) -> Result<()> {
+        (**self).disarm()
+    }
+    fn wakes_on_activity(&self) -> bool {
+        (**self).wakes_on_activity()
+    }
+}
+
+/// Combine two idle sources using the type-system. The user is only
+/// as idle as the source which saw input most recently, so this
+/// returns the lowest of the two idle times. Can be recursed for a
+/// fixed-size amount of sources.
+impl<A, B> IdleSource for (A, B)
+where
+    A: IdleSource,
+    B: IdleSource,
+{
+    fn get_idle(&self) -> Result<Duration> {
+        Ok(std::cmp::min(self.0.get_idle()?, self.1.get_idle()?))
+    }
+    fn arm(&self, idle: Duration, delay: Duration, activity: bool) -> Result<Vec<RawFd>> {
+        let mut fds = self.0.arm(idle, delay, activity)?;
+        fds.extend(self.1.arm(idle, delay, activity)?);
+        Ok(fds)
+    }
+    fn disarm(&self) -> Result<()> {
+        // Disarm both, even if the first one fails
+        let first = self.0.disarm();
+        self.1.disarm()?;
+        first
+    }
+    fn wakes_on_activity(&self) -> bool {
+        self.0.wakes_on_activity() && self.1.wakes_on_activity()
+    }
+}
+
+/// Combine multiple idle sources with a dynamic size. Like the tuple
+/// implementation, this returns the lowest idle time. An empty list
+/// is never idle.
+impl<S: IdleSource> IdleSource for Vec<S> {
+    fn get_idle(&self) -> Result<Duration> {
+        let mut min = None;
+        for source in self {
+            let idle = source.get_idle()?;
+            min = Some(min.map_or(idle, |min: Duration| min.min(idle)));
+        }
+        Ok(min.unwrap_or_default())
+    }
+    fn arm(&self, idle: Duration, delay: Duration, activity: bool) -> Result<Vec<RawFd>> {
+        let mut fds = Vec::new();
+        for source in self {
+            fds.extend(source.arm(idle, delay, activity)?);
+        }
+        Ok(fds)
+    }
+    fn disarm(&self) -> Result<()> {
+        // Disarm all of them, even if one fails
+        let mut res = Ok(());
+        for source in self {
+            let disarmed = source.disarm();
+            if res.is_ok() {
+                res = disarmed;
+            }
+        }
+        res
+    }
+    fn wakes_on_activity(&self) -> bool {
+        !self.is_empty() && self.iter().all(IdleSource::wakes_on_activity)
+    }
+}
@@ -35,7 +35,9 @@
 use std::{
     cmp,
     convert::TryInto,
-    fmt, ptr,
+    fmt,
+    os::unix::io::RawFd,
+    ptr,
     time::{Duration, Instant},
 };
 
@@ -48,17 +50,23 @@ pub type Error = Box<dyn std::error::Error>;
 /// An alias to Result which overrides the default Error type.
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+pub mod chains;
+pub mod duration;
+pub mod idle;
 pub mod modules;
+pub mod schedule;
 pub mod timers;
 
 pub use self::{
-    modules::{Module, Progress},
+    chains::Chains,
+    idle::IdleSource,
+    modules::{Event, Module, Progress},
     timers::Timer,
 };
 
 /// An identifier for a timer, based on the index in the timer list
 /// and its length.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct TimerInfo {
     /// The index of this timer in the timer list
     pub index: usize,
@@ -88,6 +96,9 @@ where
 
     /// Whether to reset on sleep
     detect_sleep: bool,
+    /// Whether the idle source wakes up the main loop when the user
+    /// becomes active, making abort urgencies unnecessary
+    activity_wakeups: bool,
 
     timers: Vec<T>,
     next_index: usize,
@@ -100,6 +111,10 @@ where
     /// If a chain is aborted during the process, store this here as
     /// to not make any more attempts to continue it.
     aborted: bool,
+
+    /// The idle source's file descriptors, for `main_async`
+    #[cfg(feature = "tokio")]
+    wakers: Wakers,
 }
 impl<T: Timer> Xidlehook<T, ()> {
     /// An empty instance without any modules
@@ -108,12 +123,16 @@ impl<T: Timer> Xidlehook<T, ()> {
             module: (),
 
             detect_sleep: false,
+            activity_wakeups: false,
 
             timers,
             next_index: 0,
             base_idle_time: Duration::default(),
             previous_idle_time: Duration::default(),
             aborted: false,
+
+            #[cfg(feature = "tokio")]
+            wakers: Wakers::default(),
         }
     }
 }
@@ -124,12 +143,16 @@ macro_rules! with_module {
             module: $module,
 
             detect_sleep: $self.detect_sleep,
+            activity_wakeups: $self.activity_wakeups,
 
             timers: $self.timers,
             next_index: $self.next_index,
             base_idle_time: $self.base_idle_time,
             previous_idle_time: $self.previous_idle_time,
             aborted: $self.aborted,
+
+            #[cfg(feature = "tokio")]
+            wakers: $self.wakers,
         }
     };
 }
@@ -171,6 +194,50 @@ where
         self
     }
 
+    /// Returns an immutable reference to the module
+    pub fn module(&self) -> &M {
+        &self.module
+    }
+
+    /// Returns a mutable reference to the module. Use this to replace modules without rebuilding
+    /// the whole xidlehook instance.
+    pub fn module_mut(&mut self) -> &mut M {
+        &mut self.module
+    }
+
+    /// Returns the index of the next timer to activate. This equals the amount of timers once the
+    /// whole chain has been activated.
+    pub fn next_index(&self) -> usize {
+        self.next_index
+    }
+
+    /// Returns whether the current chain was aborted, meaning no more timers will activate until
+    /// the user becomes active
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// Returns how long it would take for the next enabled timer to activate, given the absolute
+    /// idle time. Returns `None` if no more timers will activate in this chain.
+    pub fn time_left(&mut self, absolute_time: Duration) -> Result<Option<Duration>> {
+        // Just like in `poll`, a lower idle time means the chain starts over
+        let (mut index, relative_time) = if absolute_time < self.previous_idle_time {
+            (0, absolute_time)
+        } else if self.aborted {
+            return Ok(None);
+        } else {
+            (self.next_index, absolute_time - self.base_idle_time)
+        };
+
+        while let Some(timer) = self.timers.get_mut(index) {
+            if !timer.disabled() {
+                return Ok(Some(timer.time_left(relative_time)?.unwrap_or_default()));
+            }
+            index += 1;
+        }
+        Ok(None)
+    }
+
     /// Returns an immutable list of all timers
     pub fn timers(&self) -> &Vec<T> {
         &self.timers
@@ -183,6 +250,29 @@ where
         Ok(&mut self.timers)
     }
 
+    /// Replaces the timers that haven't activated yet, without aborting the chain or starting it
+    /// over, so that the idle time counted so far counts towards the new timers too. The timers
+    /// that already activated are kept, in place of as many of the new ones.
+    ///
+    /// # Panics
+    ///
+    /// - If there are fewer new timers than activated ones
+    pub fn replace_next_timers(&mut self, mut timers: Vec<T>) {
+        assert!(
+            timers.len() >= self.next_index,
+            "replacing the timers that already activated"
+        );
+        let next = timers.split_off(self.next_index);
+        self.timers.truncate(self.next_index);
+        self.timers.extend(next);
+    }
+
+    /// Returns a mutable reference to a single timer, if it exists. Unlike `timers_mut`, this does
+    /// not abort the idle chain, as the list of timers itself can't change.
+    pub fn timer_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.timers.get_mut(index)
+    }
+
     /// Returns the previous timer that was activated (but not deactivated)
     fn previous(&mut self) -> Option<&mut T> {
         self.next_index
@@ -190,6 +280,23 @@ where
             .map(move |i| &mut self.timers[i])
     }
 
+    /// Returns the timer info of the previous timer that was activated
+    fn previous_info(&self) -> Option<TimerInfo> {
+        self.next_index.checked_sub(1).map(|index| TimerInfo {
+            index,
+            length: self.timers.len(),
+        })
+    }
+
+    /// Notifies the modules of an event
+    fn event(&mut self, event: Event) -> Result<()> {
+        trace!("Event: {:?}", event);
+        if let Err(err) = self.module.event(event) {
+            self.module.warning(&err)?;
+        }
+        Ok(())
+    }
+
     /// Calls the abortion function on the current timer and stops pursuing the chain
     pub fn abort(&mut self) -> Result<()> {
         if self.aborted {
@@ -200,21 +307,27 @@ where
         if let Some(prev) = self.previous() {
             prev.abort()?;
         }
+        if let Some(info) = self.previous_info() {
+            self.event(Event::Aborted(info))?;
+        }
         Ok(())
     }
 
     /// Calls the abortion functions on the current timer and restarts from index zero. Just like
     /// with the `poll` function, continued usage after an error discouraged.
     pub fn reset(&mut self, absolute_time: Duration) -> Result<()> {
+        // A chain can be aborted before its first timer, by a module
+        let aborted = self.aborted;
         self.abort()?;
 
         trace!("Resetting");
 
-        if self.next_index > 0 {
+        if self.next_index > 0 || aborted {
             if let Err(err) = self.module.reset() {
                 self.module.warning(&err)?;
             }
             self.next_index = 0;
+            self.event(Event::Reset)?;
         }
 
         self.base_idle_time = absolute_time;
@@ -267,8 +380,6 @@ where
             length: self.timers.len(),
         };
 
-        let next = &mut self.timers[index];
-
         // Trigger module pre-timer
         match self.module.pre_timer(timer_info) {
             Ok(_) if force => (),
@@ -278,13 +389,21 @@ where
             },
         }
 
+        if self.next_index == 0 {
+            self.event(Event::Idle)?;
+        }
+
         // Send activation signal to current timer
-        next.activate()?;
+        self.timers[index].activate()?;
+        self.event(Event::Activated(timer_info))?;
 
         // Send deactivation signal to previous timer
         if let Some(previous) = self.previous() {
             previous.deactivate()?;
         }
+        if let Some(info) = self.previous_info() {
+            self.event(Event::Deactivated(info))?;
+        }
 
         // Reset the idle time to zero
         self.base_idle_time = absolute_time;
@@ -311,6 +430,9 @@ where
             // If the idle time has decreased, the only reasonable explanation is that the user
             // briefly wasn't idle. We reset the base idle time to zero so the entire idle duration
             // is counted.
+            if self.next_index > 0 {
+                self.event(Event::Active)?;
+            }
             self.reset(Duration::from_millis(0))?;
         }
 
@@ -320,6 +442,15 @@ where
         // user may become active (and then idle again) at any point.
         let mut max_sleep = Duration::from_nanos(u64::MAX);
 
+        // Nothing else wakes us up when a timer enables or disables
+        // itself, such as at the end of its schedule
+        for timer in &mut self.timers {
+            if let Some(change) = timer.next_change() {
+                trace!("Taking a timer's next change into account: {:?}", change);
+                max_sleep = cmp::min(max_sleep, change);
+            }
+        }
+
         let mut first_timer = 0;
 
         while let Some(timer) = self.timers.get_mut(first_timer) {
@@ -400,8 +531,10 @@ where
         }
 
         // When there's a previous timer, respect that timer's abort urgency (see
-        // `Timer::abort_urgency()`)
-        if let Some(abort) = self.previous() {
+        // `Timer::abort_urgency()`), unless the idle source will wake us up anyway
+        if self.activity_wakeups {
+            trace!("Ignoring abort urgency, the idle source wakes up on activity");
+        } else if let Some(abort) = self.previous() {
             if let Some(urgency) = abort.abort_urgency() {
                 trace!(
                     "Taking abort urgency into account. Remaining: {:?}",
@@ -418,7 +551,7 @@ where
         }
     }
 
-    /// Runs a standard poll-sleep-repeat loop.
+    /// Runs a standard poll-sleep-repeat loop, using `source` to obtain the idle time.
     /// ```rust
     /// # if std::env::var("DISPLAY").is_err() {
     /// #     // Don't fail on CI.
@@ -464,19 +597,130 @@ where
     /// xidlehook.main_sync(&xcb, || EXITED.load(Ordering::SeqCst));
     /// # Ok::<(), xidlehook_core::Error>(())
     /// ```
-    pub fn main_sync<F>(mut self, xcb: &self::modules::Xcb, mut callback: F) -> Result<()>
+    pub fn main_sync<S, F>(mut self, source: &S, callback: F) -> Result<()>
     where
+        S: IdleSource + ?Sized,
         F: FnMut() -> bool,
     {
-        loop {
-            let idle = xcb.get_idle()?;
-            match self.poll(idle)? {
-                Action::Sleep(delay) => {
-                    trace!("Sleeping for {:?}", delay);
+        run_sync(&mut self, source, callback)
+    }
+
+    /// Runs a standard poll-sleep-repeat loop... asynchronously.
+    #[cfg(any(feature = "async-std", feature = "tokio"))]
+    pub async fn main_async<S>(&mut self, source: &S) -> Result<()>
+    where
+        S: IdleSource + ?Sized,
+    {
+        run_async(self, source).await
+    }
+}
 
-                    let sleep_start = Instant::now();
+/// What the main loops drive: a single chain of timers, or several of
+/// them (see `Chains`)
+pub(crate) trait Drive {
+    /// See `Xidlehook::poll`
+    fn poll(&mut self, absolute_time: Duration) -> Result<Action>;
+    /// Tell the chains whether the idle source wakes up the main loop
+    /// when the user becomes active
+    fn set_activity_wakeups(&mut self, value: bool);
+    /// Returns true if any chain has an active timer, which has to be
+    /// aborted as soon as the user becomes active
+    fn wants_activity(&self) -> bool;
+    /// Returns true if any chain wants to be reset after a suspend
+    fn detect_sleep(&self) -> bool;
+    /// Reset the chains that want to be reset after a suspend
+    fn woke_up(&mut self, absolute_time: Duration) -> Result<()>;
+    /// The idle source's file descriptors, kept between calls to
+    /// `main_async`
+    #[cfg(feature = "tokio")]
+    fn wakers(&mut self) -> &mut Wakers;
+}
 
-                    // This sleep, unlike `thread::sleep`, will stop for signals.
+impl<T, M> Drive for Xidlehook<T, M>
+where
+    T: Timer,
+    M: Module,
+{
+    fn poll(&mut self, absolute_time: Duration) -> Result<Action> {
+        Xidlehook::poll(self, absolute_time)
+    }
+    fn set_activity_wakeups(&mut self, value: bool) {
+        self.activity_wakeups = value;
+    }
+    fn wants_activity(&self) -> bool {
+        self.next_index > 0
+    }
+    fn detect_sleep(&self) -> bool {
+        self.detect_sleep
+    }
+    fn woke_up(&mut self, absolute_time: Duration) -> Result<()> {
+        self.reset(absolute_time)
+    }
+    #[cfg(feature = "tokio")]
+    fn wakers(&mut self) -> &mut Wakers {
+        &mut self.wakers
+    }
+}
+
+/// Disarms the idle source once the main loop is done sleeping, even
+/// if it's cancelled while at it
+struct Armed<'a, S: IdleSource + ?Sized> {
+    source: &'a S,
+    fds: Vec<RawFd>,
+}
+impl<'a, S: IdleSource + ?Sized> Armed<'a, S> {
+    fn new(source: &'a S, idle: Duration, delay: Duration, activity: bool) -> Result<Self> {
+        let fds = source.arm(idle, delay, activity)?;
+        Ok(Self { source, fds })
+    }
+}
+impl<S: IdleSource + ?Sized> Drop for Armed<'_, S> {
+    fn drop(&mut self) {
+        if let Err(err) = self.source.disarm() {
+            warn!("Failed to disarm the idle source: {}", err);
+        }
+    }
+}
+
+/// The loop behind `main_sync`
+fn run_sync<D, S, F>(chain: &mut D, source: &S, mut callback: F) -> Result<()>
+where
+    D: Drive,
+    S: IdleSource + ?Sized,
+    F: FnMut() -> bool,
+{
+    chain.set_activity_wakeups(source.wakes_on_activity());
+
+    loop {
+        let idle = source.get_idle()?;
+        match chain.poll(idle)? {
+            Action::Sleep(delay) => {
+                trace!("Sleeping for {:?}", delay);
+
+                let sleep_start = Instant::now();
+
+                // These sleeps, unlike `thread::sleep`, will stop for signals.
+                let armed = Armed::new(source, idle, delay, chain.wants_activity())?;
+                if !armed.fds.is_empty() {
+                    // Round up, or we'd wake up just before the deadline
+                    let timeout = (delay + Duration::from_nanos(999_999)).as_millis();
+                    let mut pollfds: Vec<libc::pollfd> = armed
+                        .fds
+                        .iter()
+                        .map(|&fd| libc::pollfd {
+                            fd,
+                            events: libc::POLLIN,
+                            revents: 0,
+                        })
+                        .collect();
+                    unsafe {
+                        libc::poll(
+                            pollfds.as_mut_ptr(),
+                            pollfds.len().try_into().unwrap_or(libc::nfds_t::MAX),
+                            timeout.try_into().unwrap_or(libc::c_int::MAX),
+                        );
+                    }
+                } else {
                     unsafe {
                         libc::nanosleep(
                             &libc::timespec {
@@ -492,77 +736,146 @@ where
                             ptr::null_mut(),
                         );
                     }
+                }
+                drop(armed);
 
-                    if let Some(time_difference) = sleep_start.elapsed().checked_sub(delay) {
-                        if time_difference >= Duration::from_secs(3) && self.detect_sleep {
-                            info!(
-                                "We slept {:?} longer than expected - has the computer been suspended?",
-                                time_difference,
-                            );
-                            self.reset(xcb.get_idle()?)?;
-                        }
+                if let Some(time_difference) = sleep_start.elapsed().checked_sub(delay) {
+                    if time_difference >= Duration::from_secs(3) && chain.detect_sleep() {
+                        info!(
+                            "We slept {:?} longer than expected - has the computer been suspended?",
+                            time_difference,
+                        );
+                        chain.woke_up(source.get_idle()?)?;
                     }
-                },
-                Action::Forever => {
-                    warn!("xidlehook has not, and will never get, anything to do");
-                    break;
-                },
-                Action::Quit => break,
-            }
-
-            if callback() {
-                // Oh look, the callback wants us to exit
+                }
+            },
+            Action::Forever => {
+                warn!("xidlehook has not, and will never get, anything to do");
                 break;
-            }
+            },
+            Action::Quit => break,
+        }
+
+        if callback() {
+            // Oh look, the callback wants us to exit
+            break;
         }
-        Ok(())
     }
+    Ok(())
+}
 
-    /// Runs a standard poll-sleep-repeat loop... asynchronously.
-    #[cfg(any(feature = "async-std", feature = "tokio"))]
-    pub async fn main_async(&mut self, xcb: &self::modules::Xcb) -> Result<()> {
-        loop {
-            let idle = xcb.get_idle()?;
-            match self.poll(idle)? {
-                Action::Sleep(delay) => {
-                    trace!("Sleeping for {:?}", delay);
-
-                    let sleep_start = Instant::now();
-
-                    #[cfg(feature = "async-std")]
-                    async_std::task::sleep(delay).await;
-                    #[cfg(feature = "tokio")]
-                    if cfg!(not(feature = "async-std")) {
+/// The loop behind `main_async`
+#[cfg(any(feature = "async-std", feature = "tokio"))]
+async fn run_async<D, S>(chain: &mut D, source: &S) -> Result<()>
+where
+    D: Drive,
+    S: IdleSource + ?Sized,
+{
+    // Only tokio can wait on the idle source's file descriptor
+    chain.set_activity_wakeups(
+        cfg!(all(feature = "tokio", not(feature = "async-std"))) && source.wakes_on_activity(),
+    );
+
+    loop {
+        let idle = source.get_idle()?;
+        match chain.poll(idle)? {
+            Action::Sleep(delay) => {
+                trace!("Sleeping for {:?}", delay);
+
+                let sleep_start = Instant::now();
+
+                #[cfg(feature = "async-std")]
+                async_std::task::sleep(delay).await;
+                #[cfg(feature = "tokio")]
+                if cfg!(not(feature = "async-std")) {
+                    let armed = Armed::new(source, idle, delay, chain.wants_activity())?;
+                    if armed.fds.is_empty() {
                         tokio::time::sleep(delay).await;
+                    } else {
+                        chain.wakers().wait(&armed.fds, delay).await?;
                     }
+                }
 
-                    if let Some(time_difference) = sleep_start.elapsed().checked_sub(delay) {
-                        if time_difference >= Duration::from_secs(3) && self.detect_sleep {
-                            info!(
-                                "We slept {:?} longer than expected - has the computer been suspended?",
-                                time_difference,
-                            );
-                            self.reset(xcb.get_idle()?)?;
-                        }
-                    }
-                },
-                Action::Forever => {
-                    trace!("Nothing to do");
-
-                    #[cfg(feature = "async-std")]
-                    async_std::future::pending::<()>().await;
-                    #[cfg(feature = "tokio")]
-                    if cfg!(not(feature = "async-std")) {
-                        std::future::pending::<()>().await;
+                if let Some(time_difference) = sleep_start.elapsed().checked_sub(delay) {
+                    if time_difference >= Duration::from_secs(3) && chain.detect_sleep() {
+                        info!(
+                            "We slept {:?} longer than expected - has the computer been suspended?",
+                            time_difference,
+                        );
+                        chain.woke_up(source.get_idle()?)?;
                     }
-                },
-                Action::Quit => break,
+                }
+            },
+            Action::Forever => {
+                trace!("Nothing to do");
+
+                #[cfg(feature = "async-std")]
+                async_std::future::pending::<()>().await;
+                #[cfg(feature = "tokio")]
+                if cfg!(not(feature = "async-std")) {
+                    std::future::pending::<()>().await;
+                }
+            },
+            Action::Quit => break,
+        }
+    }
+    Ok(())
+}
+
+/// The idle source's file descriptors, registered with tokio once
+/// rather than every time the main loop sleeps
+#[cfg(feature = "tokio")]
+#[derive(Debug, Default)]
+pub(crate) struct Wakers {
+    fds: Vec<tokio::io::unix::AsyncFd<RawFd>>,
+}
+#[cfg(feature = "tokio")]
+impl Wakers {
+    /// Wait until any of `fds` is readable, or the delay has passed
+    async fn wait(&mut self, fds: &[RawFd], delay: Duration) -> Result<()> {
+        use tokio::io::{unix::AsyncFd, Interest};
+
+        if !self.fds.iter().map(AsyncFd::get_ref).eq(fds) {
+            self.fds.clear();
+            for &fd in fds {
+                // Newer versions of tokio deprecate this in favor of an
+                // unsafe function, as the file descriptor has to stay
+                // open. Idle sources keep theirs open for as long as
+                // they live, see `IdleSource::arm`.
+                #[allow(deprecated)]
+                let fd = AsyncFd::with_interest(fd, Interest::READABLE)?;
+                self.fds.push(fd);
             }
         }
+        if let Ok(res) = tokio::time::timeout(delay, Readable(&self.fds)).await {
+            res?;
+        }
         Ok(())
     }
 }
 
+/// Resolves once any of the file descriptors is readable
+#[cfg(feature = "tokio")]
+struct Readable<'a>(&'a [tokio::io::unix::AsyncFd<RawFd>]);
+#[cfg(feature = "tokio")]
+impl std::future::Future for Readable<'_> {
+    type Output = std::io::Result<()>;
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context,
+    ) -> std::task::Poll<Self::Output> {
+        for fd in self.0 {
+            if let std::task::Poll::Ready(guard) = fd.poll_read_ready(cx) {
+                // The idle source consumes the events in `disarm`
+                guard?.clear_ready();
+                return std::task::Poll::Ready(Ok(()));
+            }
+        }
+        std::task::Poll::Pending
+    }
+}
+
 impl<T, M> fmt::Debug for Xidlehook<T, M>
 where
     T: Timer,
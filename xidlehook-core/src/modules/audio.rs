@@ -0,0 +1,120 @@
+//! Decides which audio streams keep the chain from progressing, for
+//! both `pulse::NotWhenAudio` and `pipewire::NotWhenAudio`. By
+//! default, every stream that plays audio counts.
+
+/// What the sound server reported about a stream
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AudioStream<'a> {
+    /// The `application.name` property
+    pub application: Option<&'a str>,
+    /// The `application.process.binary` property
+    pub binary: Option<&'a str>,
+    /// Whether the stream is muted or at zero volume
+    pub muted: bool,
+    /// The name of the sink the stream plays to, or `None` for
+    /// recording streams
+    pub sink: Option<&'a str>,
+    /// Whether the stream is recording rather than playing, such as
+    /// a microphone in use during a call
+    pub capture: bool,
+    /// Whether the stream records the monitor of a sink, which only
+    /// hears what's being played, such as for a visualizer
+    pub monitor: bool,
+}
+
+/// See the module-level documentation
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AudioFilter {
+    ignore_applications: Vec<String>,
+    ignore_muted: bool,
+    sinks: Vec<String>,
+    capture: bool,
+    capture_only: bool,
+}
+impl AudioFilter {
+    /// Ignore streams whose application name or binary contains this,
+    /// ignoring case. Can be called several times.
+    pub fn with_ignored_application(mut self, application: &str) -> Self {
+        self.ignore_applications.push(application.to_lowercase());
+        self
+    }
+
+    /// Ignore streams that are muted or at zero volume
+    pub fn with_ignore_muted(mut self, ignore: bool) -> Self {
+        self.ignore_muted = ignore;
+        self
+    }
+
+    /// Only count streams playing to this sink, by name. Can be
+    /// called several times, and all sinks count if it never is.
+    pub fn with_sink(mut self, sink: &str) -> Self {
+        self.sinks.push(sink.into());
+        self
+    }
+
+    /// Also count recording streams
+    pub fn with_capture(mut self, capture: bool) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    /// Only count recording streams, to find out whether a
+    /// microphone is in use
+    pub fn with_capture_only(mut self, capture_only: bool) -> Self {
+        self.capture_only = capture_only;
+        self
+    }
+
+    /// Returns true if recording streams are counted, so the sound
+    /// server needs to be asked about them
+    pub fn capture(&self) -> bool {
+        self.capture || self.capture_only
+    }
+
+    /// Returns true if playing streams are counted
+    pub fn playback(&self) -> bool {
+        !self.capture_only
+    }
+
+    /// Returns true if muted streams are ignored
+    pub fn ignore_muted(&self) -> bool {
+        self.ignore_muted
+    }
+
+    /// The sinks to count streams on, or all of them if empty
+    pub fn sinks(&self) -> &[String] {
+        &self.sinks
+    }
+
+    /// Returns true if `stream` should keep the chain from
+    /// progressing
+    pub fn counts(&self, stream: &AudioStream) -> bool {
+        let wanted = if stream.capture {
+            self.capture()
+        } else {
+            self.playback()
+        };
+        if !wanted || (stream.capture && stream.monitor) {
+            return false;
+        }
+        if self.ignore_muted && stream.muted {
+            return false;
+        }
+        if !stream.capture && !self.sinks.is_empty() {
+            match stream.sink {
+                Some(sink) if self.sinks.iter().any(|name| name == sink) => (),
+                _ => return false,
+            }
+        }
+        let ignored = |name: Option<&str>| match name {
+            Some(name) => {
+                let name = name.to_lowercase();
+                self.ignore_applications
+                    .iter()
+                    .any(|ignored| name.contains(ignored.as_str()))
+            },
+            None => false,
+        };
+        !ignored(stream.application) && !ignored(stream.binary)
+    }
+}
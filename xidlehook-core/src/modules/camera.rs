@@ -0,0 +1,88 @@
+//! Keeps the chain from progressing while a camera is in use, such as
+//! during a video call where the user sits still. A camera counts as
+//! in use while any process holds a `/dev/video*` device open, found
+//! by looking through `/proc/*/fd`. Only the processes that xidlehook
+//! is allowed to look into are seen, which usually means the user's
+//! own.
+
+use crate::{Module, Progress, Result, TimerInfo};
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+
+/// See the module-level documentation
+pub struct NotWhenCamera {
+    proc_dir: PathBuf,
+}
+impl NotWhenCamera {
+    /// Look for cameras held open by any process in `/proc`
+    pub fn new() -> Self {
+        Self::from_proc_dir("/proc")
+    }
+
+    /// Look through a different `/proc`, such as a fake one
+    pub fn from_proc_dir<P: Into<PathBuf>>(proc_dir: P) -> Self {
+        Self {
+            proc_dir: proc_dir.into(),
+        }
+    }
+
+    /// Returns the ids of all processes that hold a camera open
+    pub fn users(&self) -> Result<Vec<u32>> {
+        let mut users = Vec::new();
+        for entry in fs::read_dir(&self.proc_dir)? {
+            let entry = entry?;
+            let pid = match entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+                Some(pid) => pid,
+                None => continue,
+            };
+            // Processes may exit, or belong to someone else
+            let fds = match fs::read_dir(entry.path().join("fd")) {
+                Ok(fds) => fds,
+                Err(_) => continue,
+            };
+            let holds_camera = fds
+                .filter_map(|fd| fs::read_link(fd.ok()?.path()).ok())
+                .any(|target| is_camera(&target));
+            if holds_camera {
+                users.push(pid);
+            }
+        }
+        users.sort_unstable();
+        Ok(users)
+    }
+}
+impl Default for NotWhenCamera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl fmt::Debug for NotWhenCamera {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NotWhenCamera")
+    }
+}
+impl Module for NotWhenCamera {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        let users = self.users()?;
+        if users.is_empty() {
+            Ok(Progress::Continue)
+        } else {
+            debug!("A camera is in use by {:?}", users);
+            // Like audio, calls can end without user interaction
+            Ok(Progress::Reset)
+        }
+    }
+}
+
+fn is_camera(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    path.parent() == Some(Path::new("/dev")) && name.starts_with("video")
+}
@@ -0,0 +1,175 @@
+//! Reads input events straight from the kernel's evdev devices
+//! (`/dev/input/event*`). This works without any display server at
+//! all, such as on the console or on kiosks. Reading these devices
+//! usually requires being in the `input` group.
+//!
+//! The kernel timestamps every event, so devices are only read when
+//! the idle time is requested. Before the first event, the user is
+//! considered active since the moment the source was created.
+
+use crate::{IdleSource, Result};
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read},
+    mem,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    ptr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, trace, warn};
+use nix::libc;
+
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const EV_ABS: u16 = 0x03;
+
+const EVENT_SIZE: usize = mem::size_of::<libc::input_event>();
+/// How many events to read at once
+const BUFFER_EVENTS: usize = 64;
+
+/// See the module-level documentation
+pub struct Evdev {
+    /// The directory to scan for new devices, if any
+    dir: Option<PathBuf>,
+    devices: RefCell<BTreeMap<PathBuf, File>>,
+    last_input: Cell<SystemTime>,
+}
+impl Evdev {
+    /// Watch all `/dev/input/event*` devices, including ones plugged
+    /// in later
+    pub fn new() -> Result<Self> {
+        Self::from_dir("/dev/input")
+    }
+
+    /// Watch all `event*` devices in a directory, including ones
+    /// added later
+    pub fn from_dir<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+        let me = Self {
+            dir: Some(dir.into()),
+            devices: RefCell::default(),
+            last_input: Cell::new(SystemTime::now()),
+        };
+        me.rescan()?;
+        if me.devices.borrow().is_empty() {
+            return Err("evdev: no readable input devices found".into());
+        }
+        Ok(me)
+    }
+
+    /// Watch only the specified devices
+    pub fn from_paths<I, P>(paths: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        let mut devices = BTreeMap::new();
+        for path in paths {
+            let path = path.into();
+            let file = open(&path)?;
+            devices.insert(path, file);
+        }
+        Ok(Self {
+            dir: None,
+            devices: RefCell::new(devices),
+            last_input: Cell::new(SystemTime::now()),
+        })
+    }
+
+    /// Open any devices in the watched directory that aren't open yet
+    fn rescan(&self) -> Result<()> {
+        let dir = match self.dir {
+            Some(ref dir) => dir,
+            None => return Ok(()),
+        };
+        let mut devices = self.devices.borrow_mut();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_event = matches!(
+                path.file_name().and_then(|name| name.to_str()),
+                Some(name) if name.starts_with("event")
+            );
+            if !is_event || devices.contains_key(&path) {
+                continue;
+            }
+            match open(&path) {
+                Ok(file) => {
+                    debug!("Watching input device {}", path.display());
+                    devices.insert(path, file);
+                },
+                Err(err) => debug!("Skipping input device {}: {}", path.display(), err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Drain all pending events, remembering the latest input
+    fn read_events(&self) {
+        let mut buf = [0; EVENT_SIZE * BUFFER_EVENTS];
+        let mut last_input = self.last_input.get();
+
+        self.devices.borrow_mut().retain(|path, file| loop {
+            let len = match file.read(&mut buf) {
+                Ok(0) => return true,
+                Ok(len) => len,
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    // Most likely ENODEV, the device was unplugged
+                    warn!("Stopped reading {}: {}", path.display(), err);
+                    return false;
+                },
+            };
+
+            for chunk in buf[..len].chunks_exact(EVENT_SIZE) {
+                // Safety: input_event is plain old data, and the chunk is exactly its size
+                let event: libc::input_event =
+                    unsafe { ptr::read_unaligned(chunk.as_ptr() as *const libc::input_event) };
+                if let EV_KEY | EV_REL | EV_ABS = event.type_ {
+                    let time = timestamp(&event);
+                    if time > last_input {
+                        trace!("Input event on {}", path.display());
+                        last_input = time;
+                    }
+                }
+            }
+        });
+
+        self.last_input.set(last_input);
+    }
+}
+impl IdleSource for Evdev {
+    fn get_idle(&self) -> Result<Duration> {
+        if let Err(err) = self.rescan() {
+            warn!("evdev: failed to scan for new devices: {}", err);
+        }
+        self.read_events();
+        Ok(SystemTime::now()
+            .duration_since(self.last_input.get())
+            .unwrap_or_default())
+    }
+}
+impl fmt::Debug for Evdev {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Evdev")
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+}
+
+fn timestamp(event: &libc::input_event) -> SystemTime {
+    #[allow(clippy::cast_sign_loss)] // the kernel never reports times before the epoch
+    let since_epoch = Duration::new(event.time.tv_sec as u64, 0)
+        + Duration::from_micros(event.time.tv_usec as u64);
+    UNIX_EPOCH + since_epoch
+}
@@ -0,0 +1,128 @@
+//! Lets anyone temporarily keep the chain from progressing, like
+//! `systemd-inhibit` or caffeine. Unlike disabling timers, an
+//! inhibitor survives user activity and stays until it's released or
+//! expires.
+
+use crate::{Module, Progress, Result, TimerInfo};
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+/// Identifies an inhibitor so that it can be released again. Never 0,
+/// as some D-Bus clients take that to mean failure.
+pub type Cookie = u32;
+
+/// Something that's currently keeping the chain from progressing
+#[derive(Clone, Debug)]
+pub struct Inhibitor {
+    /// Identifies this inhibitor
+    pub cookie: Cookie,
+    /// Why the chain is inhibited, such as "Watching a movie"
+    pub reason: String,
+    /// Who is inhibiting the chain, such as an application name
+    pub owner: String,
+    /// When this inhibitor releases itself, if ever
+    pub expires: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_cookie: Cookie,
+    inhibitors: BTreeMap<Cookie, Inhibitor>,
+}
+impl Inner {
+    fn remove_expired(&mut self) {
+        let now = Instant::now();
+        self.inhibitors
+            .retain(|_, inhibitor| match inhibitor.expires {
+                Some(expires) if expires <= now => {
+                    debug!("Inhibitor {} expired", inhibitor.cookie);
+                    false
+                },
+                _ => true,
+            });
+    }
+}
+
+/// See the module-level documentation. This is a cheap handle that
+/// can be cloned and shared with whatever hands out inhibitors, all
+/// clones share the same inhibitors.
+#[derive(Clone, Default)]
+pub struct Inhibit {
+    inner: Arc<Mutex<Inner>>,
+}
+impl Inhibit {
+    /// Returns a module without any inhibitors
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        let mut inner = self.inner.lock().expect("inhibit: poisoned lock");
+        inner.remove_expired();
+        inner
+    }
+
+    /// Keep the chain from progressing until `uninhibit` is called
+    /// with the returned cookie, or until `timeout` has passed
+    pub fn inhibit(&self, reason: String, owner: String, timeout: Option<Duration>) -> Cookie {
+        let mut inner = self.lock();
+
+        inner.next_cookie = inner.next_cookie.wrapping_add(1).max(1);
+        let cookie = inner.next_cookie;
+
+        debug!("{} inhibited the chain: {}", owner, reason);
+        inner.inhibitors.insert(
+            cookie,
+            Inhibitor {
+                cookie,
+                reason,
+                owner,
+                expires: timeout.map(|timeout| Instant::now() + timeout),
+            },
+        );
+        cookie
+    }
+
+    /// Release an inhibitor. Returns false if there was no such
+    /// inhibitor, for example because it expired.
+    pub fn uninhibit(&self, cookie: Cookie) -> bool {
+        let removed = self.lock().inhibitors.remove(&cookie);
+        if let Some(ref inhibitor) = removed {
+            debug!("{} released its inhibitor", inhibitor.owner);
+        }
+        removed.is_some()
+    }
+
+    /// Returns all active inhibitors
+    pub fn inhibitors(&self) -> Vec<Inhibitor> {
+        self.lock().inhibitors.values().cloned().collect()
+    }
+
+    /// Returns true if there is at least one active inhibitor
+    pub fn is_inhibited(&self) -> bool {
+        !self.lock().inhibitors.is_empty()
+    }
+}
+impl Module for Inhibit {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        if self.is_inhibited() {
+            // Start over, so that the chain continues as normal once
+            // the inhibitors are gone
+            Ok(Progress::Reset)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+}
+impl fmt::Debug for Inhibit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Inhibit")
+    }
+}
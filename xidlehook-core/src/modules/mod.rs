@@ -24,6 +24,26 @@ pub enum Progress {
     Stop,
 }
 
+/// Something that happened to the chain of timers, which modules
+/// are notified of through `Module::event`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The user has been idle for long enough to activate the first
+    /// timer of the chain
+    Idle,
+    /// The user became active again after being idle
+    Active,
+    /// A timer was activated
+    Activated(TimerInfo),
+    /// A timer was deactivated, because the next timer was activated
+    Deactivated(TimerInfo),
+    /// The chain was aborted while this timer was active, such as when
+    /// the user became active
+    Aborted(TimerInfo),
+    /// The chain was reset and starts over from the first timer
+    Reset,
+}
+
 /// A generic module that controls whether timers should execute or
 /// not (outside of the normal timer)
 pub trait Module {
@@ -48,6 +68,13 @@ pub trait Module {
     fn reset(&mut self) -> Result<()> {
         Ok(())
     }
+
+    /// Is called whenever something happens to the chain, such as a
+    /// timer being activated. Useful for reporting the state of
+    /// xidlehook elsewhere.
+    fn event(&mut self, _event: Event) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// The default module is also the unit type because why not
@@ -71,6 +98,9 @@ impl Module for Box<dyn Module> {
     fn reset(&mut self) -> Result<()> {
         (&mut **self).reset()
     }
+    fn event(&mut self, event: Event) -> Result<()> {
+        (&mut **self).event(event)
+    }
 }
 
 /// Combine two timers using the type-system. Can be recursed for a
@@ -102,6 +132,10 @@ where
         self.0.reset()?;
         self.1.reset()
     }
+    fn event(&mut self, event: Event) -> Result<()> {
+        self.0.event(event)?;
+        self.1.event(event)
+    }
 }
 
 /// Combine multiple modules with a dynamic size
@@ -136,13 +170,45 @@ impl<M: Module> Module for Vec<M> {
         }
         Ok(())
     }
+    fn event(&mut self, event: Event) -> Result<()> {
+        for module in self {
+            module.event(event)?;
+        }
+        Ok(())
+    }
 }
 
+pub mod audio;
+#[cfg(target_os = "linux")]
+pub mod camera;
+#[cfg(target_os = "linux")]
+pub mod evdev;
+pub mod inhibit;
+#[cfg(feature = "pipewire")]
+pub mod pipewire;
+#[cfg(target_os = "linux")]
+pub mod power;
+#[cfg(target_os = "linux")]
+pub mod process;
 #[cfg(feature = "pulse")]
 pub mod pulse;
 pub mod stop_at;
+#[cfg(feature = "wayland")]
+pub mod wayland;
 pub mod xcb;
 
+pub use self::audio::AudioFilter;
+#[cfg(target_os = "linux")]
+pub use self::camera::NotWhenCamera;
+#[cfg(target_os = "linux")]
+pub use self::evdev::Evdev;
+pub use self::inhibit::Inhibit;
+#[cfg(target_os = "linux")]
+pub use self::power::PowerSupply;
+#[cfg(target_os = "linux")]
+pub use self::process::NotWhenProcess;
 #[cfg(feature = "pulse")]
 pub use self::pulse::NotWhenAudio;
+#[cfg(feature = "wayland")]
+pub use self::wayland::Wayland;
 pub use self::{stop_at::StopAt, xcb::Xcb};
@@ -0,0 +1,315 @@
+//! Uses `PipeWire`'s native API to detect whenever audio is playing,
+//! and if so it refuses to let xidlehook run the next timer command.
+//! This works like `pulse::NotWhenAudio`, but doesn't need
+//! pipewire-pulse to be running. An `AudioFilter` can ignore
+//! applications and count recording streams, but can't look at sinks
+//! or volumes here.
+
+use super::audio::{AudioFilter, AudioStream};
+use crate::{Module, Progress, Result, TimerInfo};
+
+use libpipewire::{
+    self as pw,
+    node::{Node, NodeListener, NodeState},
+    registry::{GlobalObject, Registry},
+    spa::utils::dict::DictRef,
+    types::ObjectType,
+};
+use log::{debug, warn};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt,
+    rc::Rc,
+    sync::{mpsc, Arc, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+};
+
+/// The media class of streams that play audio, like PulseAudio's sink
+/// inputs
+const OUTPUT_STREAM: &str = "Stream/Output/Audio";
+/// The media class of streams that record audio
+const INPUT_STREAM: &str = "Stream/Input/Audio";
+
+/// The ids of all output streams which are currently running
+#[derive(Default)]
+struct Running(Mutex<HashSet<u32>>);
+impl Running {
+    fn lock(&self) -> MutexGuard<'_, HashSet<u32>> {
+        self.0.lock().expect("pipewire: poisoned lock")
+    }
+}
+
+/// See module-level docs
+pub struct NotWhenAudio {
+    running: Arc<Running>,
+    quit: pw::channel::Sender<()>,
+    thread: Option<JoinHandle<()>>,
+}
+impl NotWhenAudio {
+    /// Connect to `PipeWire` and watch its output streams from a
+    /// background thread
+    pub fn new() -> Result<Self> {
+        Self::with_filter(AudioFilter::default())
+    }
+
+    /// Like `new`, but only counts the streams that pass `filter`
+    pub fn with_filter(filter: AudioFilter) -> Result<Self> {
+        if filter.ignore_muted() || !filter.sinks().is_empty() {
+            return Err("pipewire: filtering by sink or volume is only supported by pulse".into());
+        }
+
+        let running = Arc::new(Running::default());
+        let (quit, quit_rx) = pw::channel::channel();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        let thread = {
+            let running = Arc::clone(&running);
+            thread::Builder::new()
+                .name(String::from("pipewire"))
+                .spawn(move || {
+                    if let Err(err) = watch(&running, filter, quit_rx, &ready_tx) {
+                        // Once connected, nobody is waiting for errors
+                        if ready_tx.send(Err(err.to_string())).is_err() {
+                            warn!("pipewire: {}", err);
+                        }
+                    }
+                    running.lock().clear();
+                })?
+        };
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => (),
+            Ok(Err(err)) => return Err(format!("pipewire: failed to connect: {}", err).into()),
+            Err(_) => return Err("pipewire: main loop stopped unexpectedly".into()),
+        }
+
+        Ok(Self {
+            running,
+            quit,
+            thread: Some(thread),
+        })
+    }
+}
+impl fmt::Debug for NotWhenAudio {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NotWhenAudio")
+    }
+}
+impl Drop for NotWhenAudio {
+    fn drop(&mut self) {
+        debug!("Stopping PipeWire main loop");
+        // Fails if the main loop already stopped by itself
+        let _ = self.quit.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        debug!("Stopped");
+    }
+}
+impl Module for NotWhenAudio {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        if self.running.lock().is_empty() {
+            Ok(Progress::Continue)
+        } else {
+            Ok(Progress::Reset)
+        }
+    }
+}
+
+/// Run the `PipeWire` main loop, keeping `running` up to date, until
+/// told to quit or the connection is lost
+fn watch(
+    running: &Arc<Running>,
+    filter: AudioFilter,
+    quit: pw::channel::Receiver<()>,
+    ready: &mpsc::Sender<std::result::Result<(), String>>,
+) -> std::result::Result<(), pw::Error> {
+    let mainloop = pw::main_loop::MainLoop::new(None)?;
+    let context = pw::context::Context::new(&mainloop)?;
+    let core = context.connect(None)?;
+    let registry = Rc::new(core.get_registry()?);
+
+    let _quit = quit.attach(mainloop.loop_(), {
+        let mainloop = mainloop.clone();
+        move |()| mainloop.quit()
+    });
+
+    let _core_listener = core
+        .add_listener_local()
+        .error({
+            let mainloop = mainloop.clone();
+            move |id, _seq, _res, message| {
+                // Errors on the core itself mean the connection is gone
+                if id == pw::core::PW_ID_CORE {
+                    warn!("pipewire: {}", message);
+                    mainloop.quit();
+                }
+            }
+        })
+        .register();
+
+    // Bound nodes only report their state while the proxy and its
+    // listener are alive
+    let nodes: Rc<RefCell<HashMap<u32, (Node, NodeListener)>>> = Rc::default();
+    let filter = Rc::new(filter);
+
+    let _registry_listener = registry
+        .add_listener_local()
+        .global({
+            let registry = Rc::downgrade(&registry);
+            let nodes = Rc::clone(&nodes);
+            let running = Arc::clone(running);
+            move |global| {
+                if global.type_ != ObjectType::Node {
+                    return;
+                }
+                let prop = |key: &str| global.props.and_then(|props| props.get(key));
+                let capture = match stream_kind(&filter, prop(*pw::keys::MEDIA_CLASS)) {
+                    Some(capture) => capture,
+                    None => return,
+                };
+                // The global only has some of the properties, so this
+                // can't tell that a stream counts, only that it doesn't
+                if !filter.counts(&stream(prop, capture)) {
+                    debug!("Ignoring stream {}", global.id);
+                    return;
+                }
+                if let Some(registry) = registry.upgrade() {
+                    let filter = Rc::clone(&filter);
+                    match bind_stream(&registry, global, &running, filter, capture) {
+                        Ok(node) => {
+                            nodes.borrow_mut().insert(global.id, node);
+                        },
+                        Err(err) => warn!("pipewire: failed to bind node {}: {}", global.id, err),
+                    }
+                }
+            }
+        })
+        .global_remove({
+            let running = Arc::clone(running);
+            move |id| {
+                if nodes.borrow_mut().remove(&id).is_some() && running.lock().remove(&id) {
+                    debug!("Stream {} went away", id);
+                }
+            }
+        })
+        .register();
+
+    let _ = ready.send(Ok(()));
+    mainloop.run();
+    Ok(())
+}
+
+/// Returns whether a node of this media class is a stream `filter`
+/// wants, and if so whether it's recording
+fn stream_kind(filter: &AudioFilter, media_class: Option<&str>) -> Option<bool> {
+    match media_class {
+        Some(OUTPUT_STREAM) if filter.playback() => Some(false),
+        Some(INPUT_STREAM) if filter.capture() => Some(true),
+        _ => None,
+    }
+}
+
+/// Describe a stream to the filter from its properties
+fn stream<'a>(prop: impl Fn(&str) -> Option<&'a str>, capture: bool) -> AudioStream<'a> {
+    AudioStream {
+        application: prop(*pw::keys::APP_NAME),
+        binary: prop(*pw::keys::APP_PROCESS_BINARY),
+        muted: false,
+        sink: None,
+        capture,
+        monitor: prop("stream.capture.sink") == Some("true"),
+    }
+}
+
+/// Bind to a stream and follow whether or not it's running and
+/// passes the filter
+fn bind_stream(
+    registry: &Registry,
+    global: &GlobalObject<&DictRef>,
+    running: &Arc<Running>,
+    filter: Rc<AudioFilter>,
+    capture: bool,
+) -> std::result::Result<(Node, NodeListener), pw::Error> {
+    let node: Node = registry.bind(global)?;
+    let id = global.id;
+    let running = Arc::clone(running);
+    let listener = node
+        .add_listener_local()
+        .info(move |info| {
+            let props = info.props();
+            let counts = filter.counts(&stream(
+                |key| props.and_then(|props| props.get(key)),
+                capture,
+            ));
+
+            let mut running = running.lock();
+            if counts && matches!(info.state(), NodeState::Running) {
+                if running.insert(id) {
+                    debug!("Stream {} started, total: {}", id, running.len());
+                }
+            } else if running.remove(&id) {
+                debug!("Stream {} stopped, total: {}", id, running.len());
+            }
+        })
+        .register();
+    Ok((node, listener))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn props<'a>(props: &'a [(&str, &'a str)]) -> impl Fn(&str) -> Option<&'a str> + 'a {
+        move |key| {
+            props
+                .iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| *value)
+        }
+    }
+
+    #[test]
+    fn media_classes() {
+        let filter = AudioFilter::default();
+        assert_eq!(stream_kind(&filter, Some(OUTPUT_STREAM)), Some(false));
+        assert_eq!(stream_kind(&filter, Some(INPUT_STREAM)), None);
+        assert_eq!(stream_kind(&filter, Some("Audio/Sink")), None);
+        assert_eq!(stream_kind(&filter, None), None);
+
+        let filter = AudioFilter::default().with_capture(true);
+        assert_eq!(stream_kind(&filter, Some(OUTPUT_STREAM)), Some(false));
+        assert_eq!(stream_kind(&filter, Some(INPUT_STREAM)), Some(true));
+
+        let filter = AudioFilter::default().with_capture_only(true);
+        assert_eq!(stream_kind(&filter, Some(OUTPUT_STREAM)), None);
+        assert_eq!(stream_kind(&filter, Some(INPUT_STREAM)), Some(true));
+    }
+
+    #[test]
+    fn filtered_before_binding() {
+        let filter = AudioFilter::default().with_ignored_application("dunst");
+
+        // Node globals carry the application name but not the binary
+        let global = [
+            (*pw::keys::MEDIA_CLASS, OUTPUT_STREAM),
+            (*pw::keys::APP_NAME, "Dunst"),
+        ];
+        assert!(!filter.counts(&stream(props(&global), false)));
+
+        let global = [
+            (*pw::keys::MEDIA_CLASS, OUTPUT_STREAM),
+            (*pw::keys::APP_NAME, "Firefox"),
+        ];
+        assert!(filter.counts(&stream(props(&global), false)));
+        assert!(filter.counts(&stream(props(&[]), false)));
+
+        // Once bound, the binary is known too
+        let info = [
+            (*pw::keys::APP_NAME, "Notifications"),
+            (*pw::keys::APP_PROCESS_BINARY, "dunst"),
+        ];
+        assert!(!filter.counts(&stream(props(&info), false)));
+    }
+}
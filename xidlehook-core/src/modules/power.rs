@@ -0,0 +1,109 @@
+//! Finds out whether the computer runs on AC or on battery, and how
+//! much charge is left, by reading `/sys/class/power_supply`. This
+//! lets the timers be picked by power source, such as locking sooner
+//! on battery.
+
+use crate::Result;
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// What the power supplies reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PowerStatus {
+    /// Whether an external power source, such as an AC adapter, is
+    /// connected. Computers without batteries are always on AC.
+    pub ac: bool,
+    /// The average charge of the system's batteries, in percent, or
+    /// `None` if there are none
+    pub capacity: Option<u8>,
+}
+
+/// See the module-level documentation
+pub struct PowerSupply {
+    sysfs_dir: PathBuf,
+}
+impl PowerSupply {
+    /// Read the power supplies in `/sys/class/power_supply`
+    pub fn new() -> Self {
+        Self::from_sysfs_dir("/sys/class/power_supply")
+    }
+
+    /// Read the power supplies in a different directory, such as a
+    /// fake one
+    pub fn from_sysfs_dir<P: Into<PathBuf>>(sysfs_dir: P) -> Self {
+        Self {
+            sysfs_dir: sysfs_dir.into(),
+        }
+    }
+
+    /// Read the current status of all power supplies. Batteries of
+    /// devices such as wireless mice are ignored.
+    pub fn status(&self) -> Result<PowerStatus> {
+        let mut online = false;
+        let mut batteries = 0;
+        let mut capacity = 0;
+
+        for entry in fs::read_dir(&self.sysfs_dir)? {
+            let dir = entry?.path();
+            // Supplies may go away while looking at them
+            let kind = match read_attr(&dir, "type") {
+                Ok(Some(kind)) => kind,
+                _ => continue,
+            };
+            if kind == "Battery" {
+                // Without these attributes, assume a laptop battery
+                let system = match read_attr(&dir, "scope")? {
+                    Some(scope) => scope == "System",
+                    None => true,
+                };
+                let present = match read_attr(&dir, "present")? {
+                    Some(present) => present == "1",
+                    None => true,
+                };
+                if !system || !present {
+                    continue;
+                }
+                if let Some(percent) = read_attr(&dir, "capacity")? {
+                    let percent: u32 = percent
+                        .parse()
+                        .map_err(|_| format!("invalid capacity in {}", dir.display()))?;
+                    batteries += 1;
+                    capacity += percent.min(100);
+                }
+            } else if read_attr(&dir, "online")?.as_deref() == Some("1") {
+                online = true;
+            }
+        }
+
+        // Each capacity is at most 100, and rounding down is fine
+        let capacity = capacity
+            .checked_div(batteries)
+            .map(|capacity| capacity as u8);
+        Ok(PowerStatus {
+            ac: online || capacity.is_none(),
+            capacity,
+        })
+    }
+}
+impl Default for PowerSupply {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl fmt::Debug for PowerSupply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PowerSupply")
+    }
+}
+
+/// Read a sysfs attribute, or `None` if the supply doesn't have it
+fn read_attr(dir: &Path, name: &str) -> Result<Option<String>> {
+    match fs::read_to_string(dir.join(name)) {
+        Ok(value) => Ok(Some(value.trim_end().to_owned())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
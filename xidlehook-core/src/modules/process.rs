@@ -0,0 +1,215 @@
+//! Keeps the chain from progressing while certain processes are
+//! running, such as a long `rsync`, a `cargo build` or a game
+//! launcher. Processes are found by looking through `/proc`, and
+//! matched by their name or command line using `ProcessPattern`s.
+//! xidlehook itself and the processes that started it, such as the
+//! `sh -c` running it, are never matched, even though the patterns
+//! show up in their command lines.
+
+use crate::{Error, Module, Progress, Result, TimerInfo};
+
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+};
+
+use log::debug;
+use regex::Regex;
+
+/// What a `ProcessPattern` is matched against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProcessField {
+    /// The name of the executable, as in `/proc/<pid>/comm`. The
+    /// kernel cuts it off after 15 bytes.
+    Name,
+    /// The full command line, with the arguments separated by spaces
+    Cmdline,
+}
+
+/// A regular expression that has to match somewhere in a process'
+/// name or command line. Written as `name=<regex>` or
+/// `cmdline=<regex>`, and a regex without either prefix is matched
+/// against the name.
+#[derive(Clone, Debug)]
+pub struct ProcessPattern {
+    field: ProcessField,
+    regex: Regex,
+}
+impl ProcessPattern {
+    /// Create a pattern from a regular expression
+    pub fn new(field: ProcessField, regex: &str) -> Result<Self> {
+        Ok(Self {
+            field,
+            regex: Regex::new(regex)?,
+        })
+    }
+
+    /// Returns what this pattern is matched against
+    pub fn field(&self) -> ProcessField {
+        self.field
+    }
+
+    /// Returns true if a process with this name and command line
+    /// matches
+    pub fn matches(&self, name: &str, cmdline: &str) -> bool {
+        match self.field {
+            ProcessField::Name => self.regex.is_match(name),
+            ProcessField::Cmdline => self.regex.is_match(cmdline),
+        }
+    }
+}
+impl FromStr for ProcessPattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (field, regex) = if let Some(regex) = s.strip_prefix("cmdline=") {
+            (ProcessField::Cmdline, regex)
+        } else if let Some(regex) = s.strip_prefix("name=") {
+            (ProcessField::Name, regex)
+        } else {
+            (ProcessField::Name, s)
+        };
+        if regex.is_empty() {
+            return Err("a process pattern can't be empty".into());
+        }
+        Self::new(field, regex)
+    }
+}
+impl fmt::Display for ProcessPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.field {
+            ProcessField::Name => write!(f, "name={}", self.regex),
+            ProcessField::Cmdline => write!(f, "cmdline={}", self.regex),
+        }
+    }
+}
+
+/// See the module-level documentation
+pub struct NotWhenProcess {
+    proc_dir: PathBuf,
+    patterns: Vec<ProcessPattern>,
+    uid: Option<u32>,
+    progress: Progress,
+}
+impl NotWhenProcess {
+    /// Look for processes matching any of the patterns in `/proc`
+    pub fn new(patterns: Vec<ProcessPattern>) -> Self {
+        Self::from_proc_dir("/proc", patterns)
+    }
+
+    /// Look through a different `/proc`, such as a fake one
+    pub fn from_proc_dir<P: Into<PathBuf>>(proc_dir: P, patterns: Vec<ProcessPattern>) -> Self {
+        Self {
+            proc_dir: proc_dir.into(),
+            patterns,
+            uid: None,
+            progress: Progress::Reset,
+        }
+    }
+
+    /// Only look at processes owned by this user. The default is to
+    /// look at everyone's processes.
+    pub fn with_user(mut self, uid: Option<u32>) -> Self {
+        self.uid = uid;
+        self
+    }
+
+    /// What to return while a process matches. The default is
+    /// `Progress::Reset`, since processes can exit without user
+    /// interaction, while `Progress::Abort` waits for the user to
+    /// become active again.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Returns the ids of all processes that match any pattern, other
+    /// than this one and its ancestors
+    pub fn matching_processes(&self) -> Result<Vec<u32>> {
+        let ours = self.ancestors();
+        let mut pids = Vec::new();
+        for entry in fs::read_dir(&self.proc_dir)? {
+            let entry = entry?;
+            let pid = match entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse().ok())
+            {
+                Some(pid) => pid,
+                None => continue,
+            };
+            if ours.contains(&pid) {
+                continue;
+            }
+            // Processes may exit while looking at them
+            if let Ok(true) = self.matches(&entry.path()) {
+                pids.push(pid);
+            }
+        }
+        pids.sort_unstable();
+        Ok(pids)
+    }
+
+    /// Returns the id of this process and all its parents, as far as
+    /// they can be found
+    fn ancestors(&self) -> HashSet<u32> {
+        let mut ours = HashSet::new();
+        let mut pid = process::id();
+        while pid != 0 && ours.insert(pid) {
+            pid = match self.parent(pid) {
+                Some(parent) => parent,
+                None => break,
+            };
+        }
+        ours
+    }
+
+    /// Read the parent of a process from `/proc/<pid>/stat`
+    fn parent(&self, pid: u32) -> Option<u32> {
+        let stat = fs::read_to_string(self.proc_dir.join(pid.to_string()).join("stat")).ok()?;
+        // The name is in parentheses and may contain anything, so the
+        // fields start after the last one: `<state> <ppid> ...`
+        let fields = &stat[stat.rfind(')')? + 1..];
+        fields.split_whitespace().nth(1)?.parse().ok()
+    }
+
+    fn matches(&self, dir: &Path) -> Result<bool> {
+        if let Some(uid) = self.uid {
+            if fs::metadata(dir)?.uid() != uid {
+                return Ok(false);
+            }
+        }
+
+        let name = fs::read_to_string(dir.join("comm"))?;
+        let name = name.trim_end_matches('\n');
+        // Empty for kernel threads, which only have a name
+        let cmdline = fs::read(dir.join("cmdline"))?;
+        let cmdline = String::from_utf8_lossy(&cmdline);
+        let cmdline = cmdline.trim_end_matches('\0').replace('\0', " ");
+
+        Ok(self
+            .patterns
+            .iter()
+            .any(|pattern| pattern.matches(name, &cmdline)))
+    }
+}
+impl fmt::Debug for NotWhenProcess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NotWhenProcess")
+    }
+}
+impl Module for NotWhenProcess {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        let pids = self.matching_processes()?;
+        if pids.is_empty() {
+            Ok(Progress::Continue)
+        } else {
+            debug!("Processes {:?} are running", pids);
+            Ok(self.progress)
+        }
+    }
+}
@@ -1,38 +1,167 @@
 //! Uses `PulseAudio`'s APIs to detect whenever audio is playing, and
 //! if so it refuses to let xidlehook run the next timer command. This
 //! is used to implement `--not-when-audio` in the xidlehook example
-//! application.
+//! application. Which streams count is decided by an `AudioFilter`.
 
+use super::audio::{AudioFilter, AudioStream};
 use crate::{Error, Module, Progress, Result, TimerInfo};
 
 use libpulse_binding::{
     callbacks::ListResult,
     context::{self, subscribe::Facility, Context, State},
     mainloop::threaded::Mainloop,
+    proplist::{properties, Proplist},
 };
 use log::debug;
 use std::{
     cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
     fmt,
     rc::Rc,
 };
 
 const PA_NAME: &str = "xidlehook";
 
+#[derive(Default)]
 struct Counter {
     in_progress: Cell<usize>,
     last_total: Cell<usize>,
 }
+impl Counter {
+    fn add(&self, name: &str) {
+        let count = self.in_progress.get().saturating_add(1);
+        self.in_progress.set(count);
+        debug!("Partial {} count: {}", name, count);
+    }
+    fn finish(&self, name: &str) {
+        let count = self.in_progress.replace(0);
+        self.last_total.set(count);
+        debug!("Total {} sum: {}", name, count);
+    }
+}
+
+struct Streams {
+    filter: AudioFilter,
+    /// Sink names by index, only kept when filtering by sink
+    sinks: RefCell<HashMap<u32, String>>,
+    /// The indices of the sources that are monitors of sinks, only
+    /// kept when counting recording streams
+    monitors: RefCell<HashSet<u32>>,
+    playback: Counter,
+    capture: Counter,
+}
+impl Streams {
+    fn count(&self, proplist: &Proplist, stream: AudioStream) -> bool {
+        let application = proplist.get_str(properties::APPLICATION_NAME);
+        let binary = proplist.get_str(properties::APPLICATION_PROCESS_BINARY);
+        self.filter.counts(&AudioStream {
+            application: application.as_deref(),
+            binary: binary.as_deref(),
+            ..stream
+        })
+    }
+}
+
+/// Ask `PulseAudio` for a fresh count of the streams that pass the
+/// filter
+fn count_streams(ctx: &mut Context, streams: &Rc<Streams>) {
+    let introspect = ctx.introspect();
+
+    // Replies arrive in order, so the names are known before the
+    // sink inputs are counted
+    if streams.filter.playback() && !streams.filter.sinks().is_empty() {
+        streams.sinks.borrow_mut().clear();
+        let streams = Rc::clone(streams);
+        introspect.get_sink_info_list(move |res| {
+            if let ListResult::Item(item) = res {
+                if let Some(ref name) = item.name {
+                    streams
+                        .sinks
+                        .borrow_mut()
+                        .insert(item.index, name.to_string());
+                }
+            }
+        });
+    }
+
+    if streams.filter.playback() {
+        let streams = Rc::clone(streams);
+        introspect.get_sink_input_info_list(move |res| match res {
+            ListResult::Item(item) => {
+                if item.corked {
+                    return;
+                }
+                let sinks = streams.sinks.borrow();
+                let sink = sinks.get(&item.sink).map(String::as_str);
+                let stream = AudioStream {
+                    muted: item.mute || item.volume.is_muted(),
+                    sink,
+                    ..AudioStream::default()
+                };
+                if streams.count(&item.proplist, stream) {
+                    streams.playback.add("playback");
+                }
+            },
+            ListResult::End | ListResult::Error => streams.playback.finish("playback"),
+        });
+    }
+
+    if streams.filter.capture() {
+        streams.monitors.borrow_mut().clear();
+        let streams = Rc::clone(streams);
+        introspect.get_source_info_list(move |res| {
+            if let ListResult::Item(item) = res {
+                let monitor = item.monitor_of_sink.is_some()
+                    || item
+                        .name
+                        .as_ref()
+                        .filter(|name| name.ends_with(".monitor"))
+                        .is_some();
+                if monitor {
+                    streams.monitors.borrow_mut().insert(item.index);
+                }
+            }
+        });
+    }
+
+    if streams.filter.capture() {
+        let streams = Rc::clone(streams);
+        introspect.get_source_output_info_list(move |res| match res {
+            ListResult::Item(item) => {
+                if item.corked {
+                    return;
+                }
+                let stream = AudioStream {
+                    muted: item.mute || item.volume.is_muted(),
+                    capture: true,
+                    monitor: streams.monitors.borrow().contains(&item.source),
+                    ..AudioStream::default()
+                };
+                if streams.count(&item.proplist, stream) {
+                    streams.capture.add("capture");
+                }
+            },
+            ListResult::End | ListResult::Error => streams.capture.finish("capture"),
+        });
+    }
+}
 
 /// See module-level docs
 pub struct NotWhenAudio {
-    counter: Rc<Counter>,
+    streams: Rc<Streams>,
     ctx: Rc<RefCell<Context>>,
     mainloop: Rc<RefCell<Mainloop>>,
 }
 impl NotWhenAudio {
-    /// Connect to `PulseAudio` and subscribe to notification of changes
+    /// Connect to `PulseAudio` and subscribe to notification of
+    /// changes, counting every playing stream
     pub fn new() -> Result<Self> {
+        Self::with_filter(AudioFilter::default())
+    }
+
+    /// Connect to `PulseAudio` and subscribe to notification of
+    /// changes, counting the streams that pass `filter`
+    pub fn with_filter(filter: AudioFilter) -> Result<Self> {
         let mainloop = Rc::new(RefCell::new(
             Mainloop::new().ok_or("pulseaudio: failed to create main loop")?,
         ));
@@ -96,56 +225,54 @@ impl NotWhenAudio {
         }
         ctx.borrow_mut().set_state_callback(None);
 
-        let counter = Rc::new(Counter {
-            in_progress: Cell::new(0),
-            last_total: Cell::new(0),
-        });
-
-        // Closure for setting up async count of input sinks
-        let get_sinks = |ctx: &mut Context, counter: Rc<Counter>| {
-            ctx.introspect()
-                .get_sink_input_info_list(move |res| match res {
-                    ListResult::Item(item) => {
-                        if !item.corked {
-                            let count = counter.in_progress.get().saturating_add(1);
-                            counter.in_progress.set(count);
-                            debug!("Partial count: {}", count);
-                        }
-                    },
-                    ListResult::End | ListResult::Error => {
-                        let count = counter.in_progress.replace(0);
-                        counter.last_total.set(count);
-                        debug!("Total sum: {}", count);
-                    },
-                });
+        // Only the facilities the filter needs are subscribed to, and
+        // it always needs either playback or recording streams
+        let mut interest = if filter.playback() {
+            Facility::SinkInput.to_interest_mask()
+        } else {
+            Facility::SourceOutput.to_interest_mask()
         };
+        if filter.playback() && !filter.sinks().is_empty() {
+            interest |= Facility::Sink.to_interest_mask();
+        }
+        if filter.capture() {
+            interest |=
+                Facility::SourceOutput.to_interest_mask() | Facility::Source.to_interest_mask();
+        }
+
+        let streams = Rc::new(Streams {
+            filter,
+            sinks: RefCell::default(),
+            monitors: RefCell::default(),
+            playback: Counter::default(),
+            capture: Counter::default(),
+        });
 
         // Setup notification callback
         //
         // Upon notification of a change, we will make use of introspection
-        // to obtain a fresh count of active input sinks.
+        // to obtain a fresh count of active streams.
         {
             let ctx_ref = Rc::clone(&ctx);
-            let counter_ref = Rc::clone(&counter);
+            let streams_ref = Rc::clone(&streams);
 
             ctx.borrow_mut()
                 .set_subscribe_callback(Some(Box::new(move |_, _, _| {
                     let ctx_ref = unsafe { &mut *ctx_ref.as_ptr() }; // Borrow checker workaround
-                    get_sinks(ctx_ref, Rc::clone(&counter_ref));
+                    count_streams(ctx_ref, &streams_ref);
                 })));
         }
 
-        // Subscribe to sink input events
-        ctx.borrow_mut()
-            .subscribe(Facility::SinkInput.to_interest_mask(), |_| ());
+        // Subscribe to stream events
+        ctx.borrow_mut().subscribe(interest, |_| ());
 
         // Check if audio is already playing
-        get_sinks(&mut ctx.borrow_mut(), Rc::clone(&counter));
+        count_streams(&mut ctx.borrow_mut(), &streams);
 
         mainloop.borrow_mut().unlock();
 
         Ok(Self {
-            counter,
+            streams,
             ctx,
             mainloop,
         })
@@ -169,7 +296,12 @@ impl Drop for NotWhenAudio {
 impl Module for NotWhenAudio {
     fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
         self.mainloop.borrow_mut().lock();
-        let players = self.counter.last_total.get();
+        let players = self
+            .streams
+            .playback
+            .last_total
+            .get()
+            .saturating_add(self.streams.capture.last_total.get());
         self.mainloop.borrow_mut().unlock();
         if players == 0 {
             Ok(Progress::Continue)
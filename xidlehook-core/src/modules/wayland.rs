@@ -0,0 +1,235 @@
+//! Wayland support. Unlike X11, Wayland compositors don't let clients
+//! query the idle time directly. Instead, the `Wayland` idle source
+//! asks the compositor to notify it once the user has been idle for a
+//! short timeout, using `ext-idle-notify-v1` or the older KDE
+//! `org_kde_kwin_idle` protocol, and counts the time from there.
+//!
+//! This means the idle time reads as zero until the timeout has
+//! passed, so keep the timeout shorter than your first timer. It also
+//! means there's no way to ask how long the user has already been
+//! idle when connecting.
+
+use crate::{IdleSource, Result};
+
+use std::{
+    convert::TryInto,
+    fmt,
+    os::unix::net::UnixStream,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+use wayland_client::{
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_registry::WlRegistry, wl_seat::WlSeat},
+    Connection, Dispatch, QueueHandle,
+};
+use wayland_protocols::ext::idle_notify::v1::client::{
+    ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+    ext_idle_notifier_v1::ExtIdleNotifierV1,
+};
+use wayland_protocols_plasma::idle::client::{
+    org_kde_kwin_idle::OrgKdeKwinIdle,
+    org_kde_kwin_idle_timeout::{self, OrgKdeKwinIdleTimeout},
+};
+
+#[derive(Debug, Default)]
+struct Shared {
+    /// When the user became idle, if they currently are
+    idle_since: Option<Instant>,
+    /// Set if the event thread died
+    error: Option<String>,
+}
+
+struct State {
+    shared: Arc<Mutex<Shared>>,
+    timeout: Duration,
+}
+impl State {
+    fn idled(&mut self) {
+        debug!("Compositor reported the user idle");
+        let now = Instant::now();
+        let mut shared = self.shared.lock().expect("wayland: poisoned lock");
+        // The compositor only tells us after the timeout has passed
+        shared.idle_since = Some(now.checked_sub(self.timeout).unwrap_or(now));
+    }
+    fn resumed(&mut self) {
+        debug!("Compositor reported the user active");
+        self.shared
+            .lock()
+            .expect("wayland: poisoned lock")
+            .idle_since = None;
+    }
+}
+
+/// See the module-level documentation
+pub struct Wayland {
+    shared: Arc<Mutex<Shared>>,
+    protocol: &'static str,
+}
+impl Wayland {
+    /// Connect to the compositor using the default timeout of one
+    /// second
+    pub fn new() -> Result<Self> {
+        Self::with_timeout(Duration::from_secs(1))
+    }
+
+    /// Connect to the compositor and ask to be notified after the user
+    /// has been idle for `timeout`. Events are read on a separate
+    /// thread so that idle notifications are timestamped as soon as
+    /// they arrive.
+    pub fn with_timeout(timeout: Duration) -> Result<Self> {
+        Self::from_connection(Connection::connect_to_env()?, timeout)
+    }
+
+    /// Like `with_timeout`, but talk to the compositor over an already
+    /// connected socket instead of the one in `WAYLAND_DISPLAY`
+    pub fn from_socket(socket: UnixStream, timeout: Duration) -> Result<Self> {
+        Self::from_connection(Connection::from_socket(socket)?, timeout)
+    }
+
+    fn from_connection(conn: Connection, timeout: Duration) -> Result<Self> {
+        let (globals, mut queue) = registry_queue_init::<State>(&conn)?;
+        let qh = queue.handle();
+
+        let timeout_ms: u32 = timeout.as_millis().try_into()?;
+
+        let seat: WlSeat = globals.bind(&qh, 1..=1, ())?;
+
+        let protocol = if let Ok(notifier) = globals.bind::<ExtIdleNotifierV1, _, _>(&qh, 1..=1, ())
+        {
+            notifier.get_idle_notification(timeout_ms, &seat, &qh, ());
+            "ext-idle-notify-v1"
+        } else if let Ok(idle) = globals.bind::<OrgKdeKwinIdle, _, _>(&qh, 1..=1, ()) {
+            idle.get_idle_timeout(&seat, timeout_ms, &qh, ());
+            "org_kde_kwin_idle"
+        } else {
+            return Err(
+                "wayland: compositor supports neither ext-idle-notify-v1 nor org_kde_kwin_idle"
+                    .into(),
+            );
+        };
+        debug!("Using the {} protocol", protocol);
+
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let mut state = State {
+            shared: Arc::clone(&shared),
+            timeout,
+        };
+        queue.roundtrip(&mut state)?;
+
+        thread::Builder::new()
+            .name(String::from("xidlehook-wayland"))
+            .spawn(move || loop {
+                if let Err(err) = queue.blocking_dispatch(&mut state) {
+                    warn!("Wayland event thread stopped: {}", err);
+                    state.shared.lock().expect("wayland: poisoned lock").error =
+                        Some(err.to_string());
+                    break;
+                }
+            })?;
+
+        Ok(Self { shared, protocol })
+    }
+
+    /// Returns the name of the protocol used to detect idleness
+    pub fn protocol(&self) -> &'static str {
+        self.protocol
+    }
+}
+impl IdleSource for Wayland {
+    fn get_idle(&self) -> Result<Duration> {
+        let shared = self.shared.lock().expect("wayland: poisoned lock");
+        if let Some(ref err) = shared.error {
+            return Err(format!("wayland: connection lost: {}", err).into());
+        }
+        Ok(shared
+            .idle_since
+            .map(|since| since.elapsed())
+            .unwrap_or_default())
+    }
+}
+impl fmt::Debug for Wayland {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Wayland")
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as wayland_client::Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+impl Dispatch<WlSeat, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlSeat,
+        _: <WlSeat as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+impl Dispatch<ExtIdleNotifierV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ExtIdleNotifierV1,
+        _: <ExtIdleNotifierV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+impl Dispatch<ExtIdleNotificationV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_idle_notification_v1::Event::Idled => state.idled(),
+            ext_idle_notification_v1::Event::Resumed => state.resumed(),
+            _ => (),
+        }
+    }
+}
+impl Dispatch<OrgKdeKwinIdle, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &OrgKdeKwinIdle,
+        _: <OrgKdeKwinIdle as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+impl Dispatch<OrgKdeKwinIdleTimeout, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &OrgKdeKwinIdleTimeout,
+        event: org_kde_kwin_idle_timeout::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            org_kde_kwin_idle_timeout::Event::Idle => state.idled(),
+            org_kde_kwin_idle_timeout::Event::Resumed => state.resumed(),
+            _ => (),
+        }
+    }
+}
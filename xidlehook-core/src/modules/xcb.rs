@@ -1,17 +1,55 @@
-//! Various X-related utilities. The `Xcb` object must be used
-//! regardless of whether or not you want to use `NotWhenAudio` - it's
-//! xidlehook's simple way to obtain the idle time. The
+//! Various X-related utilities. The `Xcb` object is xidlehook's
+//! default `IdleSource`, it's the simple way to obtain the idle time
+//! on X11. The
 //! `NotWhenFullscreen` module is used to implement
-//! `--not-when-fullscreen` in the example client.
+//! `--not-when-fullscreen` in the example client (see `FullscreenMode`
+//! for which windows it looks at, and `OutputSelector` for which
+//! monitors), and `NotWhenWindow`
+//! is used to implement `--not-when-window` using `WindowRule`s.
+//! `Xcb::pointer_corner` finds out whether the pointer is in a
+//! `Corner`, for hot corners.
+//!
+//! With `Xcb::enable_idle_alarms`, the X server's `IDLETIME` counter
+//! from the XSync extension is used to wake up the main loop the
+//! moment the user becomes active, instead of polling.
 
-use crate::{Module, Progress, Result, TimerInfo};
+use crate::{Error, IdleSource, Module, Progress, Result, TimerInfo};
 
-use std::{fmt, rc::Rc, slice, time::Duration};
+use std::{
+    cell::{Cell, RefCell},
+    cmp,
+    convert::TryInto,
+    fmt,
+    os::unix::io::{AsRawFd, RawFd},
+    rc::Rc,
+    slice,
+    str::FromStr,
+    time::Duration,
+};
 
-use log::debug;
+use log::{debug, trace};
 
 const NET_WM_STATE: &str = "_NET_WM_STATE";
 const NET_WM_STATE_FULLSCREEN: &str = "_NET_WM_STATE_FULLSCREEN";
+const NET_WM_NAME: &str = "_NET_WM_NAME";
+const NET_ACTIVE_WINDOW: &str = "_NET_ACTIVE_WINDOW";
+const NET_WM_DESKTOP: &str = "_NET_WM_DESKTOP";
+const NET_CURRENT_DESKTOP: &str = "_NET_CURRENT_DESKTOP";
+/// The `_NET_WM_DESKTOP` of windows that are on all desktops
+const ALL_DESKTOPS: u32 = 0xFFFF_FFFF;
+const WM_WINDOW_ROLE: &str = "WM_WINDOW_ROLE";
+const IDLETIME: &str = "IDLETIME";
+
+/// The XSync alarms used to wake up the main loop
+struct IdleAlarms {
+    /// The event code of the first XSync event
+    first_event: u8,
+    counter: xcb::sync::Counter,
+    /// Fires when the user becomes active again
+    active: xcb::sync::Alarm,
+    /// Fires when the idle time reaches the next deadline
+    threshold: xcb::sync::Alarm,
+}
 
 /// See the crate-level documentation
 pub struct Xcb {
@@ -19,6 +57,17 @@ pub struct Xcb {
     root_window: xcb::Window,
     atom_net_wm_state: xcb::Atom,
     atom_net_wm_state_fullscreen: xcb::Atom,
+    atom_net_wm_name: xcb::Atom,
+    atom_net_active_window: xcb::Atom,
+    atom_wm_window_role: xcb::Atom,
+    atom_net_wm_desktop: xcb::Atom,
+    atom_net_current_desktop: xcb::Atom,
+    alarms: Option<IdleAlarms>,
+    /// The outputs `pointer_corner` looks at, until RandR says they
+    /// changed
+    corner_outputs: RefCell<Option<Vec<Output>>>,
+    /// Whether RandR has been asked to report output changes
+    watching_outputs: Cell<bool>,
 }
 impl Xcb {
     /// Initialize all the things, like setting up an X connection.
@@ -29,19 +78,32 @@ impl Xcb {
         let screen = setup.roots().next().ok_or("no xcb root")?;
         let root_window = screen.root();
 
-        let atom_net_wm_state = xcb::xproto::intern_atom(&conn, false, NET_WM_STATE)
-            .get_reply()?
-            .atom();
-        let atom_net_wm_state_fullscreen =
-            xcb::xproto::intern_atom(&conn, false, NET_WM_STATE_FULLSCREEN)
+        let intern_atom = |name| -> Result<xcb::Atom> {
+            Ok(xcb::xproto::intern_atom(&conn, false, name)
                 .get_reply()?
-                .atom();
+                .atom())
+        };
+        let atom_net_wm_state = intern_atom(NET_WM_STATE)?;
+        let atom_net_wm_state_fullscreen = intern_atom(NET_WM_STATE_FULLSCREEN)?;
+        let atom_net_wm_name = intern_atom(NET_WM_NAME)?;
+        let atom_net_active_window = intern_atom(NET_ACTIVE_WINDOW)?;
+        let atom_wm_window_role = intern_atom(WM_WINDOW_ROLE)?;
+        let atom_net_wm_desktop = intern_atom(NET_WM_DESKTOP)?;
+        let atom_net_current_desktop = intern_atom(NET_CURRENT_DESKTOP)?;
 
         Ok(Self {
             conn,
             root_window,
             atom_net_wm_state,
             atom_net_wm_state_fullscreen,
+            atom_net_wm_name,
+            atom_net_active_window,
+            atom_wm_window_role,
+            atom_net_wm_desktop,
+            atom_net_current_desktop,
+            alarms: None,
+            corner_outputs: RefCell::new(None),
+            watching_outputs: Cell::new(false),
         })
     }
     /// Get the user's idle time using the `XScreenSaver` plugin
@@ -50,39 +112,451 @@ impl Xcb {
         Ok(Duration::from_millis(info.ms_since_user_input().into()))
     }
 
-    fn query_fullscreen(&self, root: xcb::Window) -> Result<bool> {
+    /// Use XSync alarms on the `IDLETIME` counter to let the main loop
+    /// sleep until the user becomes active or the next timer is due,
+    /// instead of waking up regularly to check. Fails if the X server
+    /// doesn't support it, in which case polling is used.
+    pub fn enable_idle_alarms(&mut self) -> Result<()> {
+        let first_event = self
+            .conn
+            .get_extension_data(xcb::sync::id())
+            .filter(|ext| ext.present())
+            .ok_or("the X server lacks the SYNC extension")?
+            .first_event();
+
+        xcb::sync::initialize(&self.conn, 3, 1).get_reply()?;
+
+        let counter = xcb::sync::list_system_counters(&self.conn)
+            .get_reply()?
+            .counters()
+            .find(|counter| counter.name() == IDLETIME)
+            .ok_or("the X server has no IDLETIME counter")?
+            .counter();
+
+        let active = self.conn.generate_id();
+        let threshold = self.conn.generate_id();
+        for &alarm in &[active, threshold] {
+            xcb::sync::create_alarm_checked(&self.conn, alarm, 0, None).request_check()?;
+        }
+
+        debug!("Using XSync idle alarms");
+        self.alarms = Some(IdleAlarms {
+            first_event,
+            counter,
+            active,
+            threshold,
+        });
+        Ok(())
+    }
+
+    /// Point an alarm at the `IDLETIME` counter, and choose whether it
+    /// sends any events
+    fn change_alarm(
+        &self,
+        alarms: &IdleAlarms,
+        alarm: xcb::sync::Alarm,
+        test_type: xcb::sync::Testtype,
+        value: Duration,
+        events: bool,
+    ) {
+        let value: i64 = value.as_millis().try_into().unwrap_or(i64::MAX);
+        // All fields are set, so this struct matches the value list
+        // the X server expects.
+        let values = xcb::ffi::sync::xcb_sync_change_alarm_value_list_t {
+            counter: alarms.counter,
+            valueType: xcb::sync::VALUETYPE_ABSOLUTE,
+            value: xcb::ffi::sync::xcb_sync_int64_t {
+                hi: (value >> 32) as i32,
+                lo: value as u32,
+            },
+            testType: test_type,
+            delta: xcb::ffi::sync::xcb_sync_int64_t { hi: 0, lo: 0 },
+            events: events.into(),
+        };
+        let mask = xcb::sync::CA_COUNTER
+            | xcb::sync::CA_VALUE_TYPE
+            | xcb::sync::CA_VALUE
+            | xcb::sync::CA_TEST_TYPE
+            | xcb::sync::CA_DELTA
+            | xcb::sync::CA_EVENTS;
+        unsafe {
+            xcb::ffi::sync::xcb_sync_change_alarm(self.conn.get_raw_conn(), alarm, mask, &values);
+        }
+    }
+
+    fn is_fullscreen(&self, window: xcb::Window) -> Result<bool> {
+        let prop = xcb::xproto::get_property(
+            &self.conn,             // c
+            false,                  // delete
+            window,                 // window
+            self.atom_net_wm_state, // property
+            xcb::xproto::ATOM_ATOM, // type_
+            0,                      // long_offset
+            u32::max_value(),       // long_length
+        )
+        .get_reply()?;
+
+        // The safe API can't possibly know what value xcb returned,
+        // sadly. Here we are manually transmuting &[c_void] to
+        // &[Atom], as we specified we want an atom.
+        let value = prop.value();
+
+        let value = unsafe {
+            slice::from_raw_parts(value.as_ptr() as *const xcb::xproto::Atom, value.len())
+        };
+
+        Ok(value
+            .iter()
+            .any(|atom| *atom == self.atom_net_wm_state_fullscreen))
+    }
+
+    /// Call `found` with every fullscreen window below `root`, until
+    /// it returns true
+    fn query_fullscreen<F>(
+        &self,
+        root: xcb::Window,
+        mode: FullscreenMode,
+        current_desktop: Option<u32>,
+        found: &mut F,
+    ) -> Result<bool>
+    where
+        F: FnMut(xcb::Window) -> Result<bool>,
+    {
         let windows = xcb::xproto::query_tree(&self.conn, root).get_reply()?;
 
         for &window in windows.children() {
-            let prop = xcb::xproto::get_property(
-                &self.conn,             // c
-                false,                  // delete
-                window,                 // window
-                self.atom_net_wm_state, // property
-                xcb::xproto::ATOM_ATOM, // type_
-                0,                      // long_offset
-                u32::max_value(),       // long_length
-            )
-            .get_reply()?;
+            if mode == FullscreenMode::Visible && !self.is_visible(window, current_desktop)? {
+                continue;
+            }
+
+            if self.is_fullscreen(window)? {
+                debug!("Window {} was fullscreen", window);
+                if found(window)? {
+                    return Ok(true);
+                }
+            }
+
+            if self.query_fullscreen(window, mode, current_desktop, found)? {
+                return Ok(true);
+            }
+        }
 
-            // The safe API can't possibly know what value xcb returned,
-            // sadly. Here we are manually transmuting &[c_void] to
-            // &[Atom], as we specified we want an atom.
-            let value = prop.value();
+        Ok(false)
+    }
 
-            let value = unsafe {
-                slice::from_raw_parts(value.as_ptr() as *const xcb::xproto::Atom, value.len())
+    /// Call `found` with every fullscreen window that `mode` selects,
+    /// until it returns true
+    fn for_each_fullscreen<F>(&self, mode: FullscreenMode, mut found: F) -> Result<bool>
+    where
+        F: FnMut(xcb::Window) -> Result<bool>,
+    {
+        if mode == FullscreenMode::Focused {
+            return match self.focused_window()? {
+                Some(window) if self.is_fullscreen(window)? => {
+                    debug!("Focused window {} was fullscreen", window);
+                    found(window)
+                },
+                _ => Ok(false),
             };
+        }
+
+        for screen in self.conn.get_setup().roots() {
+            let current_desktop =
+                self.cardinal_property(screen.root(), self.atom_net_current_desktop)?;
+            if self.query_fullscreen(screen.root(), mode, current_desktop, &mut found)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Get whether or not any window is fullscreen. This is the same
+    /// as `get_fullscreen_with(FullscreenMode::Any)`.
+    pub fn get_fullscreen(&self) -> Result<bool> {
+        self.get_fullscreen_with(FullscreenMode::Any)
+    }
 
-            if value
+    /// Get whether or not a window is fullscreen, looking only at the
+    /// windows that `mode` selects
+    pub fn get_fullscreen_with(&self, mode: FullscreenMode) -> Result<bool> {
+        self.for_each_fullscreen(mode, |_| Ok(true))
+    }
+
+    /// Get the outputs which show a fullscreen window, looking only
+    /// at the windows that `mode` selects. A window is shown on the
+    /// output its center is on.
+    pub fn get_fullscreen_outputs(&self, mode: FullscreenMode) -> Result<Vec<Output>> {
+        let outputs = self.outputs()?;
+        let mut fullscreen: Vec<Output> = Vec::new();
+
+        self.for_each_fullscreen(mode, |window| {
+            let (root, x, y) = self.window_center(window)?;
+            let output = outputs
                 .iter()
-                .any(|atom| *atom == self.atom_net_wm_state_fullscreen)
-            {
-                debug!("Window {} was fullscreen", window);
+                .find(|output| output.root == root && output.contains(x, y));
+            if let Some(output) = output {
+                trace!("Window {} is on output {}", window, output.name);
+                if !fullscreen.contains(output) {
+                    fullscreen.push(output.clone());
+                }
+            }
+            Ok(false)
+        })?;
+
+        Ok(fullscreen)
+    }
+
+    /// Get all enabled outputs (monitors) of all screens, using RandR
+    pub fn outputs(&self) -> Result<Vec<Output>> {
+        self.conn
+            .get_extension_data(xcb::randr::id())
+            .filter(|ext| ext.present())
+            .ok_or("the X server lacks the RandR extension")?;
+        // Primary outputs and the current screen resources need 1.3
+        xcb::randr::query_version(&self.conn, 1, 3).get_reply()?;
+
+        let mut outputs = Vec::new();
+        for screen in self.conn.get_setup().roots() {
+            let root = screen.root();
+            let resources =
+                xcb::randr::get_screen_resources_current(&self.conn, root).get_reply()?;
+            let primary = xcb::randr::get_output_primary(&self.conn, root)
+                .get_reply()?
+                .output();
+
+            for &output in resources.outputs() {
+                let info =
+                    xcb::randr::get_output_info(&self.conn, output, resources.config_timestamp())
+                        .get_reply()?;
+                // Disconnected or disabled outputs show nothing
+                if info.connection() != xcb::randr::CONNECTION_CONNECTED as u8 || info.crtc() == 0 {
+                    continue;
+                }
+                let crtc = xcb::randr::get_crtc_info(
+                    &self.conn,
+                    info.crtc(),
+                    resources.config_timestamp(),
+                )
+                .get_reply()?;
+
+                outputs.push(Output {
+                    name: String::from_utf8_lossy(info.name()).into_owned(),
+                    primary: output == primary,
+                    root,
+                    x: crtc.x(),
+                    y: crtc.y(),
+                    width: crtc.width(),
+                    height: crtc.height(),
+                });
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Get the root window a window belongs to, along with where its
+    /// center is relative to it
+    // Rounding the center down is fine
+    #[allow(clippy::integer_division)]
+    fn window_center(&self, window: xcb::Window) -> Result<(xcb::Window, i32, i32)> {
+        let geometry = xcb::xproto::get_geometry(&self.conn, window).get_reply()?;
+        let root = geometry.root();
+        let position =
+            xcb::xproto::translate_coordinates(&self.conn, window, root, 0, 0).get_reply()?;
+        Ok((
+            root,
+            i32::from(position.dst_x()) + i32::from(geometry.width()) / 2,
+            i32::from(position.dst_y()) + i32::from(geometry.height()) / 2,
+        ))
+    }
+
+    /// Get which corner of a monitor the pointer is in, if any. A
+    /// corner is a square of `size` by `size` pixels. Without RandR,
+    /// the whole screen counts as one monitor.
+    ///
+    /// The outputs are only queried once and then cached until RandR
+    /// reports a change, which is noticed by reading the pending X
+    /// events. Since that would also swallow the events of
+    /// `enable_idle_alarms`, poll this on a separate connection.
+    pub fn pointer_corner(&self, size: u16) -> Result<Option<Corner>> {
+        self.watch_outputs()?;
+        while let Some(event) = self.conn.poll_for_event() {
+            self.handle_output_event(event.response_type() & !0x80);
+        }
+
+        let pointer = xcb::xproto::query_pointer(&self.conn, self.root_window).get_reply()?;
+        if !pointer.same_screen() {
+            return Ok(None);
+        }
+        let (x, y) = (pointer.root_x().into(), pointer.root_y().into());
+
+        let mut cache = self.corner_outputs.borrow_mut();
+        let outputs = cache.get_or_insert_with(|| {
+            self.outputs().unwrap_or_else(|err| {
+                trace!("Looking at the whole screen for corners: {}", err);
+                Vec::new()
+            })
+        });
+        if outputs.is_empty() {
+            let geometry = xcb::xproto::get_geometry(&self.conn, self.root_window).get_reply()?;
+            return Ok(Corner::at(x, y, geometry.width(), geometry.height(), size));
+        }
+        Ok(outputs
+            .iter()
+            .find(|output| output.root == pointer.root() && output.contains(x, y))
+            .and_then(|output| output.corner(x, y, size)))
+    }
+
+    /// Ask RandR to send an event whenever the outputs change, so
+    /// `pointer_corner` knows when to query them again
+    fn watch_outputs(&self) -> Result<()> {
+        if self.watching_outputs.replace(true) {
+            return Ok(());
+        }
+        let randr = self.conn.get_extension_data(xcb::randr::id());
+        if randr.filter(|ext| ext.present()).is_none() {
+            return Ok(());
+        }
+        xcb::randr::query_version(&self.conn, 1, 3).get_reply()?;
+        let mask = xcb::randr::NOTIFY_MASK_SCREEN_CHANGE
+            | xcb::randr::NOTIFY_MASK_CRTC_CHANGE
+            | xcb::randr::NOTIFY_MASK_OUTPUT_CHANGE;
+        for screen in self.conn.get_setup().roots() {
+            xcb::randr::select_input_checked(&self.conn, screen.root(), mask as u16)
+                .request_check()?;
+        }
+        Ok(())
+    }
+
+    /// Forget the cached outputs if an X event of kind `kind` says
+    /// they changed
+    fn handle_output_event(&self, kind: u8) {
+        let first_event = match self.conn.get_extension_data(xcb::randr::id()) {
+            Some(ext) if ext.present() => ext.first_event(),
+            _ => return,
+        };
+        if kind == first_event + xcb::randr::SCREEN_CHANGE_NOTIFY
+            || kind == first_event + xcb::randr::NOTIFY
+        {
+            debug!("The outputs changed, looking them up again");
+            self.corner_outputs.replace(None);
+        }
+    }
+
+    /// Return a `NotWhenFullscreen` instance for a reference-counted
+    /// self
+    pub fn not_when_fullscreen(self: Rc<Self>) -> NotWhenFullscreen {
+        NotWhenFullscreen {
+            xcb: self,
+            mode: FullscreenMode::default(),
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Get a single number property, such as `_NET_WM_DESKTOP`
+    fn cardinal_property(&self, window: xcb::Window, property: xcb::Atom) -> Result<Option<u32>> {
+        let prop = xcb::xproto::get_property(
+            &self.conn,
+            false,
+            window,
+            property,
+            xcb::xproto::ATOM_CARDINAL,
+            0,
+            1,
+        )
+        .get_reply()?;
+        if prop.format() != 32 {
+            return Ok(None);
+        }
+        Ok(prop.value::<u32>().first().copied())
+    }
+
+    /// Get whether or not a window is mapped, including all its
+    /// parents. Windows that have been destroyed aren't.
+    fn is_viewable(&self, window: xcb::Window) -> bool {
+        match xcb::xproto::get_window_attributes(&self.conn, window).get_reply() {
+            Ok(attributes) => attributes.map_state() == xcb::xproto::MAP_STATE_VIEWABLE as u8,
+            Err(_) => false,
+        }
+    }
+
+    /// Get whether or not a window is viewable and on the current
+    /// desktop, if the window manager has desktops
+    fn is_visible(&self, window: xcb::Window, current_desktop: Option<u32>) -> Result<bool> {
+        if !self.is_viewable(window) {
+            return Ok(false);
+        }
+        let desktop = self.cardinal_property(window, self.atom_net_wm_desktop)?;
+        Ok(match (desktop, current_desktop) {
+            (Some(desktop), Some(current)) => desktop == current || desktop == ALL_DESKTOPS,
+            _ => true,
+        })
+    }
+
+    /// Get all the strings in a text property, such as both the
+    /// instance and class name in `WM_CLASS`
+    fn text_property(&self, window: xcb::Window, property: xcb::Atom) -> Result<Vec<String>> {
+        let prop = xcb::xproto::get_property(
+            &self.conn,
+            false,
+            window,
+            property,
+            xcb::xproto::ATOM_ANY,
+            0,
+            u32::MAX,
+        )
+        .get_reply()?;
+        if prop.format() != 8 {
+            return Ok(Vec::new());
+        }
+        Ok(prop
+            .value::<u8>()
+            .split(|&byte| byte == 0)
+            .filter(|part| !part.is_empty())
+            .map(|part| String::from_utf8_lossy(part).into_owned())
+            .collect())
+    }
+
+    /// Returns the first rule that matches the window, if any
+    fn match_window<'a>(
+        &self,
+        window: xcb::Window,
+        rules: &'a [WindowRule],
+    ) -> Result<Option<&'a WindowRule>> {
+        for rule in rules {
+            let values = match rule.property {
+                WindowProperty::Class => self.text_property(window, xcb::xproto::ATOM_WM_CLASS)?,
+                WindowProperty::Title => {
+                    let title = self.text_property(window, self.atom_net_wm_name)?;
+                    if title.is_empty() {
+                        self.text_property(window, xcb::xproto::ATOM_WM_NAME)?
+                    } else {
+                        title
+                    }
+                },
+                WindowProperty::Role => self.text_property(window, self.atom_wm_window_role)?,
+            };
+            if values.iter().any(|value| rule.matches(value)) {
+                return Ok(Some(rule));
+            }
+        }
+        Ok(None)
+    }
+
+    fn query_window(&self, root: xcb::Window, rules: &[WindowRule]) -> Result<bool> {
+        let windows = xcb::xproto::query_tree(&self.conn, root).get_reply()?;
+
+        for &window in windows.children() {
+            // Children of unmapped windows can't be visible either
+            if !self.is_viewable(window) {
+                continue;
+            }
+
+            if let Some(rule) = self.match_window(window, rules)? {
+                debug!("Window {} matched {}", window, rule);
                 return Ok(true);
             }
 
-            if self.query_fullscreen(window)? {
+            if self.query_window(window, rules)? {
                 return Ok(true);
             }
         }
@@ -90,21 +564,139 @@ impl Xcb {
         Ok(false)
     }
 
-    /// Get whether or not the user's currently active window is
-    /// fullscreen
-    pub fn get_fullscreen(&self) -> Result<bool> {
+    /// Get the window the user is currently using, according to the
+    /// window manager or otherwise the input focus
+    fn focused_window(&self) -> Result<Option<xcb::Window>> {
+        let prop = xcb::xproto::get_property(
+            &self.conn,
+            false,
+            self.root_window,
+            self.atom_net_active_window,
+            xcb::xproto::ATOM_WINDOW,
+            0,
+            1,
+        )
+        .get_reply()?;
+        if prop.format() == 32 {
+            if let Some(&window) = prop.value::<xcb::Window>().first() {
+                if window != xcb::NONE {
+                    return Ok(Some(window));
+                }
+            }
+        }
+
+        let window = xcb::xproto::get_input_focus(&self.conn)
+            .get_reply()?
+            .focus();
+        if window == xcb::NONE || window == xcb::xproto::INPUT_FOCUS_POINTER_ROOT {
+            Ok(None)
+        } else {
+            Ok(Some(window))
+        }
+    }
+
+    /// Get whether or not any visible window matches any of the rules
+    pub fn get_window_matches(&self, rules: &[WindowRule]) -> Result<bool> {
         for screen in self.conn.get_setup().roots() {
-            if self.query_fullscreen(screen.root())? {
+            if self.query_window(screen.root(), rules)? {
                 return Ok(true);
             }
         }
         Ok(false)
     }
 
-    /// Return a `NotWhenFullscreen` instance for a reference-counted
-    /// self
-    pub fn not_when_fullscreen(self: Rc<Self>) -> NotWhenFullscreen {
-        NotWhenFullscreen { xcb: self }
+    /// Get whether or not the user's currently active window matches
+    /// any of the rules
+    pub fn get_focused_window_matches(&self, rules: &[WindowRule]) -> Result<bool> {
+        let window = match self.focused_window()? {
+            Some(window) => window,
+            None => return Ok(false),
+        };
+        if let Some(rule) = self.match_window(window, rules)? {
+            debug!("Focused window {} matched {}", window, rule);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Return a `NotWhenWindow` instance for a reference-counted self,
+    /// which looks at all visible windows
+    pub fn not_when_window(self: Rc<Self>, rules: Vec<WindowRule>) -> NotWhenWindow {
+        NotWhenWindow {
+            xcb: self,
+            rules,
+            focused: false,
+        }
+    }
+
+    /// Return a `NotWhenWindow` instance for a reference-counted self,
+    /// which only looks at the focused window
+    pub fn not_when_focused_window(self: Rc<Self>, rules: Vec<WindowRule>) -> NotWhenWindow {
+        NotWhenWindow {
+            xcb: self,
+            rules,
+            focused: true,
+        }
+    }
+}
+impl IdleSource for Xcb {
+    fn get_idle(&self) -> Result<Duration> {
+        Xcb::get_idle(self)
+    }
+    fn arm(&self, idle: Duration, delay: Duration, activity: bool) -> Result<Vec<RawFd>> {
+        let alarms = match self.alarms {
+            Some(ref alarms) => alarms,
+            None => return Ok(Vec::new()),
+        };
+
+        // Anything already queued is left over from a previous wakeup
+        while self.conn.poll_for_queued_event().is_some() {}
+
+        // Comparisons are used rather than transitions, because they
+        // also fire if the counter has already passed the value, such
+        // as when the user moved after `idle` was measured. The active
+        // alarm would fire on every input event, so it's only turned
+        // on when asked to.
+        self.change_alarm(
+            alarms,
+            alarms.active,
+            xcb::sync::TESTTYPE_NEGATIVE_COMPARISON,
+            cmp::max(idle, Duration::from_millis(1)),
+            activity,
+        );
+        self.change_alarm(
+            alarms,
+            alarms.threshold,
+            xcb::sync::TESTTYPE_POSITIVE_COMPARISON,
+            idle + delay,
+            true,
+        );
+        if !self.conn.flush() {
+            return Err("failed to flush the X connection".into());
+        }
+
+        Ok(vec![self.conn.as_raw_fd()])
+    }
+    fn disarm(&self) -> Result<()> {
+        let alarms = match self.alarms {
+            Some(ref alarms) => alarms,
+            None => return Ok(()),
+        };
+        while let Some(event) = self.conn.poll_for_event() {
+            let kind = event.response_type() & !0x80;
+            if kind == alarms.first_event + xcb::sync::ALARM_NOTIFY {
+                trace!("Woken up by an idle alarm");
+            } else if kind == 0 {
+                debug!("Ignoring X error while waiting for idle alarms");
+            } else {
+                self.handle_output_event(kind);
+            }
+        }
+        self.conn.has_error()?;
+        Ok(())
+    }
+    fn wakes_on_activity(&self) -> bool {
+        self.alarms.is_some()
     }
 }
 impl fmt::Debug for Xcb {
@@ -113,19 +705,218 @@ impl fmt::Debug for Xcb {
     }
 }
 
+/// Which windows `NotWhenFullscreen` looks at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FullscreenMode {
+    /// Every window, including minimized ones and ones on other
+    /// desktops
+    Any,
+    /// Only windows which are mapped and on the current desktop
+    Visible,
+    /// Only the focused window, `_NET_ACTIVE_WINDOW`
+    Focused,
+}
+#[allow(clippy::derivable_impls)] // #[default] needs a newer compiler
+impl Default for FullscreenMode {
+    fn default() -> Self {
+        FullscreenMode::Any
+    }
+}
+impl FromStr for FullscreenMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "any" => Ok(FullscreenMode::Any),
+            "visible" => Ok(FullscreenMode::Visible),
+            "focused" => Ok(FullscreenMode::Focused),
+            _ => Err(format!(
+                "invalid fullscreen mode {:?}, expected any, visible or focused",
+                s
+            )
+            .into()),
+        }
+    }
+}
+
+/// An output (monitor) as reported by RandR
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Output {
+    /// The name of the output, such as "eDP-1" or "HDMI-1"
+    pub name: String,
+    /// Whether this is the primary output
+    pub primary: bool,
+    /// The root window of the screen this output belongs to
+    pub root: xcb::Window,
+    /// The position of the output's top-left corner on the root window
+    pub x: i16,
+    /// See `x`
+    pub y: i16,
+    /// The size of the output, in pixels
+    pub width: u16,
+    /// See `width`
+    pub height: u16,
+}
+impl Output {
+    /// Get whether or not a point on the root window is on this
+    /// output
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let (left, top) = (i32::from(self.x), i32::from(self.y));
+        x >= left
+            && x < left + i32::from(self.width)
+            && y >= top
+            && y < top + i32::from(self.height)
+    }
+
+    /// Get which corner of this output, if any, a point on the root
+    /// window is in. See `Corner::at`.
+    pub fn corner(&self, x: i32, y: i32, size: u16) -> Option<Corner> {
+        Corner::at(
+            x - i32::from(self.x),
+            y - i32::from(self.y),
+            self.width,
+            self.height,
+            size,
+        )
+    }
+}
+
+/// Which outputs `NotWhenFullscreen` cares about: either the primary
+/// output, or an output by name
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutputSelector {
+    /// The output marked as primary, written as "primary"
+    Primary,
+    /// The output with this exact name
+    Name(String),
+}
+impl OutputSelector {
+    /// Get whether or not this selects `output`
+    pub fn matches(&self, output: &Output) -> bool {
+        match self {
+            OutputSelector::Primary => output.primary,
+            OutputSelector::Name(name) => output.name == *name,
+        }
+    }
+}
+impl FromStr for OutputSelector {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "" => Err("an output name can't be empty".into()),
+            "primary" => Ok(OutputSelector::Primary),
+            name => Ok(OutputSelector::Name(name.into())),
+        }
+    }
+}
+impl fmt::Display for OutputSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputSelector::Primary => write!(f, "primary"),
+            OutputSelector::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A corner of the screen, as used for hot corners
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corner {
+    /// Written as "top-left"
+    TopLeft,
+    /// Written as "top-right"
+    TopRight,
+    /// Written as "bottom-left"
+    BottomLeft,
+    /// Written as "bottom-right"
+    BottomRight,
+}
+impl Corner {
+    /// Get which corner, if any, a point is in on a screen of this
+    /// size. A corner is a square of `size` by `size` pixels.
+    pub fn at(x: i32, y: i32, width: u16, height: u16, size: u16) -> Option<Self> {
+        let (width, height, size) = (i32::from(width), i32::from(height), i32::from(size));
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return None;
+        }
+        let left = x < size;
+        let right = x >= width - size;
+        let top = y < size;
+        let bottom = y >= height - size;
+        match (left || right, top || bottom) {
+            (true, true) => Some(match (left, top) {
+                (true, true) => Corner::TopLeft,
+                (false, true) => Corner::TopRight,
+                (true, false) => Corner::BottomLeft,
+                (false, false) => Corner::BottomRight,
+            }),
+            _ => None,
+        }
+    }
+}
+impl FromStr for Corner {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "top-left" => Ok(Corner::TopLeft),
+            "top-right" => Ok(Corner::TopRight),
+            "bottom-left" => Ok(Corner::BottomLeft),
+            "bottom-right" => Ok(Corner::BottomRight),
+            _ => Err(format!(
+                "invalid corner {:?}, expected top-left, top-right, bottom-left or bottom-right",
+                s
+            )
+            .into()),
+        }
+    }
+}
+impl fmt::Display for Corner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Corner::TopLeft => write!(f, "top-left"),
+            Corner::TopRight => write!(f, "top-right"),
+            Corner::BottomLeft => write!(f, "bottom-left"),
+            Corner::BottomRight => write!(f, "bottom-right"),
+        }
+    }
+}
+
 /// See the module-level documentation
 pub struct NotWhenFullscreen {
     xcb: Rc<Xcb>,
+    mode: FullscreenMode,
+    outputs: Vec<OutputSelector>,
+}
+impl NotWhenFullscreen {
+    /// Choose which windows to look at. The default is all of them.
+    pub fn with_mode(mut self, mode: FullscreenMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Only look at fullscreen windows on these outputs. The default,
+    /// an empty list, is all of them.
+    pub fn with_outputs(mut self, outputs: Vec<OutputSelector>) -> Self {
+        self.outputs = outputs;
+        self
+    }
 }
 impl Module for NotWhenFullscreen {
     fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
-        self.xcb.get_fullscreen().map(|fullscreen| {
-            if fullscreen {
-                Progress::Abort
-            } else {
-                Progress::Continue
-            }
-        })
+        let fullscreen = if self.outputs.is_empty() {
+            self.xcb.get_fullscreen_with(self.mode)?
+        } else {
+            self.xcb
+                .get_fullscreen_outputs(self.mode)?
+                .iter()
+                .any(|output| self.outputs.iter().any(|selector| selector.matches(output)))
+        };
+        if fullscreen {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
     }
 }
 impl fmt::Debug for NotWhenFullscreen {
@@ -133,3 +924,100 @@ impl fmt::Debug for NotWhenFullscreen {
         write!(f, "NotWhenFullscreen")
     }
 }
+
+/// The window property that a `WindowRule` looks at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowProperty {
+    /// `WM_CLASS`, which matches on either the instance or the class
+    /// name
+    Class,
+    /// `_NET_WM_NAME`, falling back to `WM_NAME`
+    Title,
+    /// `WM_WINDOW_ROLE`
+    Role,
+}
+
+/// Matches windows whose property contains a value, ignoring case.
+/// Can be parsed from strings like `class=zoom` or `title=meeting`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WindowRule {
+    property: WindowProperty,
+    value: String,
+}
+impl WindowRule {
+    /// Returns a rule which matches windows whose `property` contains
+    /// `value`
+    pub fn new(property: WindowProperty, value: &str) -> Self {
+        Self {
+            property,
+            value: value.to_lowercase(),
+        }
+    }
+    /// Returns the property this rule looks at
+    pub fn property(&self) -> WindowProperty {
+        self.property
+    }
+    /// Returns whether or not the property value matches this rule
+    pub fn matches(&self, value: &str) -> bool {
+        value.to_lowercase().contains(&self.value)
+    }
+}
+impl FromStr for WindowRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.splitn(2, '=');
+        let property = match parts.next() {
+            Some("class") => WindowProperty::Class,
+            Some("title") => WindowProperty::Title,
+            Some("role") => WindowProperty::Role,
+            _ => {
+                return Err(format!(
+                    "invalid window rule {:?}, expected class=..., title=... or role=...",
+                    s
+                )
+                .into())
+            },
+        };
+        match parts.next() {
+            Some(value) if !value.is_empty() => Ok(Self::new(property, value)),
+            _ => Err(format!("window rule {:?} has nothing to match", s).into()),
+        }
+    }
+}
+impl fmt::Display for WindowRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let property = match self.property {
+            WindowProperty::Class => "class",
+            WindowProperty::Title => "title",
+            WindowProperty::Role => "role",
+        };
+        write!(f, "{}={}", property, self.value)
+    }
+}
+
+/// See the module-level documentation
+pub struct NotWhenWindow {
+    xcb: Rc<Xcb>,
+    rules: Vec<WindowRule>,
+    focused: bool,
+}
+impl Module for NotWhenWindow {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        let matches = if self.focused {
+            self.xcb.get_focused_window_matches(&self.rules)?
+        } else {
+            self.xcb.get_window_matches(&self.rules)?
+        };
+        if matches {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+}
+impl fmt::Debug for NotWhenWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NotWhenWindow")
+    }
+}
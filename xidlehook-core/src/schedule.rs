@@ -0,0 +1,393 @@
+//! Wall-clock schedules, such as `mon-fri 09:00-18:00`, which decide
+//! when a timer is enabled. A `Scheduled` timer is disabled outside of
+//! its schedule, so that for example suspending can be skipped during
+//! work hours, or happen sooner at night. The time is read from a
+//! `Clock`, which is the local time by default. Since nothing else
+//! happens when a period starts or ends, `Timer::next_change` tells
+//! xidlehook to wake up by then.
+
+use crate::{timers::Timer, Error, Result};
+
+use std::{cmp, io, mem, str::FromStr, time::Duration};
+
+use log::warn;
+use nix::libc;
+
+/// The number of minutes in a day
+const DAY: u16 = 24 * 60;
+
+/// A day of the week
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weekday {
+    /// Written as "mon"
+    Monday,
+    /// Written as "tue"
+    Tuesday,
+    /// Written as "wed"
+    Wednesday,
+    /// Written as "thu"
+    Thursday,
+    /// Written as "fri"
+    Friday,
+    /// Written as "sat"
+    Saturday,
+    /// Written as "sun"
+    Sunday,
+}
+impl Weekday {
+    /// All days, starting with Monday
+    pub const ALL: [Self; 7] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+        Weekday::Sunday,
+    ];
+
+    /// Returns the day before this one
+    pub fn previous(self) -> Self {
+        Self::ALL[(self.index() + 6) % 7]
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+impl FromStr for Weekday {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mon" => Ok(Weekday::Monday),
+            "tue" => Ok(Weekday::Tuesday),
+            "wed" => Ok(Weekday::Wednesday),
+            "thu" => Ok(Weekday::Thursday),
+            "fri" => Ok(Weekday::Friday),
+            "sat" => Ok(Weekday::Saturday),
+            "sun" => Ok(Weekday::Sunday),
+            _ => Err(format!("invalid day {:?}, expected mon, tue, ..., sun", s).into()),
+        }
+    }
+}
+
+/// A point in the week, as shown on a wall clock
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalTime {
+    /// The day of the week
+    pub weekday: Weekday,
+    /// The hour, from 0 to 23
+    pub hour: u8,
+    /// The minute, from 0 to 59
+    pub minute: u8,
+    /// The second, from 0 to 59
+    pub second: u8,
+}
+impl LocalTime {
+    /// Returns the number of minutes since midnight
+    fn minutes(self) -> u16 {
+        u16::from(self.hour) * 60 + u16::from(self.minute)
+    }
+
+    /// Returns the start of the minute this many minutes later
+    // Splitting minutes into days, hours and minutes
+    #[allow(clippy::integer_division)]
+    fn plus_minutes(self, minutes: u32) -> Self {
+        let day = u32::from(DAY);
+        let total = self.weekday.index() as u32 * day + u32::from(self.minutes()) + minutes;
+        let minutes = total % day;
+        Self {
+            weekday: Weekday::ALL[(total / day % 7) as usize],
+            hour: (minutes / 60) as u8,
+            minute: (minutes % 60) as u8,
+            second: 0,
+        }
+    }
+}
+
+/// Tells a schedule what time it is. Implement this to use another
+/// time zone, or a fake time in tests.
+pub trait Clock {
+    /// Returns the current time
+    fn now(&self) -> Result<LocalTime>;
+}
+
+/// The system's local time, as set by `TZ` or `/etc/localtime`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> Result<LocalTime> {
+        let time = unsafe { libc::time(std::ptr::null_mut()) };
+        let mut tm: libc::tm = unsafe { mem::zeroed() };
+        if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+            return Err(io::Error::last_os_error().into());
+        }
+        // tm_wday starts with Sunday
+        let weekday = Weekday::ALL[(tm.tm_wday as usize + 6) % 7];
+        Ok(LocalTime {
+            weekday,
+            hour: tm.tm_hour as u8,
+            minute: tm.tm_min as u8,
+            // Leap seconds are 60
+            second: cmp::min(tm.tm_sec, 59) as u8,
+        })
+    }
+}
+
+/// A recurring period of time, written as days and/or a time range,
+/// such as `mon-fri 09:00-18:00`, `sat,sun` or `22:00-07:00`. Without
+/// days, it's every day, and without a time range, it's the whole day.
+/// A time range that ends before it starts lasts past midnight, into
+/// the next day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Period {
+    /// One bit for each day, starting with Monday
+    days: u8,
+    /// Minutes since midnight. Equal to `end` for the whole day.
+    start: u16,
+    /// See `start`
+    end: u16,
+}
+impl Period {
+    /// Returns true if `time` is within this period
+    pub fn contains(&self, time: LocalTime) -> bool {
+        let on = |weekday: Weekday| self.days & 1 << weekday.index() != 0;
+        let minutes = time.minutes();
+        if self.start == self.end {
+            on(time.weekday)
+        } else if self.start < self.end {
+            on(time.weekday) && minutes >= self.start && minutes < self.end
+        } else {
+            (on(time.weekday) && minutes >= self.start)
+                || (on(time.weekday.previous()) && minutes < self.end)
+        }
+    }
+}
+impl FromStr for Period {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut days = None;
+        let mut times = None;
+        for part in s.split_whitespace() {
+            if part.contains(':') && times.is_none() {
+                times = Some(parse_times(part)?);
+            } else if !part.contains(':') && days.is_none() {
+                days = Some(parse_days(part)?);
+            } else {
+                return Err(
+                    format!("invalid period {:?}, expected days and a time range", s).into(),
+                );
+            }
+        }
+        if days.is_none() && times.is_none() {
+            return Err("a period can't be empty".into());
+        }
+        let (start, end) = times.unwrap_or((0, 0));
+        Ok(Self {
+            days: days.unwrap_or(0x7F),
+            start,
+            end,
+        })
+    }
+}
+
+/// Parse days such as `mon-fri` or `sat,sun` into one bit for each day
+fn parse_days(s: &str) -> Result<u8> {
+    let mut days = 0;
+    for range in s.split(',') {
+        let (first, last) = match range.find('-') {
+            Some(i) => (range[..i].parse()?, range[i + 1..].parse()?),
+            None => {
+                let day = range.parse()?;
+                (day, day)
+            },
+        };
+        // Ranges such as fri-mon wrap around the week
+        let mut day: Weekday = first;
+        loop {
+            days |= 1 << day.index();
+            if day == last {
+                break;
+            }
+            day = Weekday::ALL[(day.index() + 1) % 7];
+        }
+    }
+    Ok(days)
+}
+
+/// Parse a time range such as `09:00-18:00` into minutes since
+/// midnight. `24:00` is allowed as the end.
+fn parse_times(s: &str) -> Result<(u16, u16)> {
+    let i = s
+        .find('-')
+        .ok_or_else(|| format!("invalid time range {:?}, expected e.g. 09:00-18:00", s))?;
+    let start = parse_time(&s[..i])?;
+    let end = parse_time(&s[i + 1..])?;
+    if start == DAY {
+        return Err(format!("invalid time range {:?}, 24:00 can only be the end", s).into());
+    }
+    Ok((start, end % DAY))
+}
+
+fn parse_time(s: &str) -> Result<u16> {
+    let invalid = || format!("invalid time {:?}, expected e.g. 09:00", s);
+    let i = s.find(':').ok_or_else(invalid)?;
+    let hour: u16 = s[..i].parse().map_err(|_| invalid())?;
+    let minute: u16 = s[i + 1..].parse().map_err(|_| invalid())?;
+    if hour > 24 || minute >= 60 || hour * 60 + minute > DAY {
+        return Err(invalid().into());
+    }
+    Ok(hour * 60 + minute)
+}
+
+/// When something is enabled: during any of the `during` periods (or
+/// always, if there are none), but never during a `not_during` period
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Schedule<C: Clock = SystemClock> {
+    clock: C,
+    during: Vec<Period>,
+    not_during: Vec<Period>,
+}
+impl Schedule {
+    /// Returns a schedule that's always active, using the local time
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl<C: Clock> Schedule<C> {
+    /// Use another clock, such as a fake one
+    pub fn with_clock<D: Clock>(self, clock: D) -> Schedule<D> {
+        Schedule {
+            clock,
+            during: self.during,
+            not_during: self.not_during,
+        }
+    }
+
+    /// Only be active during this period, or any other one added this
+    /// way
+    pub fn with_during(mut self, period: Period) -> Self {
+        self.during.push(period);
+        self
+    }
+
+    /// Never be active during this period
+    pub fn with_not_during(mut self, period: Period) -> Self {
+        self.not_during.push(period);
+        self
+    }
+
+    /// Returns true if there are no periods, so it's always active
+    pub fn is_empty(&self) -> bool {
+        self.during.is_empty() && self.not_during.is_empty()
+    }
+
+    /// Returns true if the schedule is active at this time
+    pub fn active_at(&self, time: LocalTime) -> bool {
+        (self.during.is_empty() || self.during.iter().any(|period| period.contains(time)))
+            && !self.not_during.iter().any(|period| period.contains(time))
+    }
+
+    /// Returns how long it is until the schedule turns on or off, or
+    /// `None` if it never does
+    pub fn next_change_at(&self, time: LocalTime) -> Option<Duration> {
+        let active = self.active_at(time);
+        // Periods only start or end at these times of the day
+        let mut minutes: Vec<u16> = self
+            .during
+            .iter()
+            .chain(&self.not_during)
+            .flat_map(|period| [0, period.start, period.end])
+            .collect();
+        minutes.sort_unstable();
+        minutes.dedup();
+
+        let now = u32::from(time.minutes());
+        // A week and a day, for periods that last past midnight
+        for day in 0..=7 {
+            for &minute in &minutes {
+                let at = day * u32::from(DAY) + u32::from(minute);
+                if at <= now {
+                    continue;
+                }
+                let offset = at - now;
+                if self.active_at(time.plus_minutes(offset)) != active {
+                    let seconds = u64::from(offset) * 60 - u64::from(time.second);
+                    return Some(Duration::from_secs(seconds));
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns how long it is until the schedule turns on or off, or
+    /// `None` if it never does or the clock can't be read
+    pub fn next_change(&self) -> Option<Duration> {
+        if self.is_empty() {
+            return None;
+        }
+        // `active` already warns about broken clocks
+        self.clock
+            .now()
+            .ok()
+            .and_then(|time| self.next_change_at(time))
+    }
+
+    /// Returns true if the schedule is active right now. If the clock
+    /// can't be read, it's active.
+    pub fn active(&self) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        match self.clock.now() {
+            Ok(time) => self.active_at(time),
+            Err(err) => {
+                warn!("Failed to read the clock: {}", err);
+                true
+            },
+        }
+    }
+}
+
+/// A timer which is disabled outside of its schedule
+#[derive(Debug)]
+pub struct Scheduled<T: Timer, C: Clock = SystemClock> {
+    /// The timer that's run during the schedule
+    pub timer: T,
+    /// When the timer is enabled
+    pub schedule: Schedule<C>,
+}
+impl<T: Timer, C: Clock> Scheduled<T, C> {
+    /// Only enable `timer` while `schedule` is active
+    pub fn new(timer: T, schedule: Schedule<C>) -> Self {
+        Self { timer, schedule }
+    }
+}
+impl<T: Timer, C: Clock> Timer for Scheduled<T, C> {
+    fn time_left(&mut self, idle_time: Duration) -> Result<Option<Duration>> {
+        self.timer.time_left(idle_time)
+    }
+    fn abort_urgency(&self) -> Option<Duration> {
+        self.timer.abort_urgency()
+    }
+    fn activate(&mut self) -> Result<()> {
+        self.timer.activate()
+    }
+    fn abort(&mut self) -> Result<()> {
+        self.timer.abort()
+    }
+    fn deactivate(&mut self) -> Result<()> {
+        self.timer.deactivate()
+    }
+    fn disabled(&mut self) -> bool {
+        self.timer.disabled() || !self.schedule.active()
+    }
+    fn next_change(&mut self) -> Option<Duration> {
+        match (self.timer.next_change(), self.schedule.next_change()) {
+            (Some(timer), Some(schedule)) => Some(cmp::min(timer, schedule)),
+            (timer, schedule) => timer.or(schedule),
+        }
+    }
+}
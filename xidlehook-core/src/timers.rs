@@ -40,6 +40,13 @@ pub trait Timer {
     fn disabled(&mut self) -> bool {
         false
     }
+    /// Return how long it is until `disabled` may change by itself,
+    /// such as when a schedule starts or ends, so that xidlehook
+    /// doesn't sleep past it. `None` means that it only changes along
+    /// with something else which wakes up xidlehook.
+    fn next_change(&mut self) -> Option<Duration> {
+        None
+    }
 }
 
 /// A simple timer that runs a binary executable after a certain
@@ -0,0 +1,144 @@
+use xidlehook_core::modules::audio::{AudioFilter, AudioStream};
+
+fn playing(application: &str) -> AudioStream<'_> {
+    AudioStream {
+        application: Some(application),
+        binary: Some("firefox"),
+        sink: Some("alsa_output.usb-headset"),
+        ..AudioStream::default()
+    }
+}
+
+#[test]
+fn default() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let filter = AudioFilter::default();
+    assert!(filter.counts(&playing("Firefox")));
+    assert!(filter.counts(&AudioStream {
+        muted: true,
+        ..playing("Firefox")
+    }));
+    assert!(filter.counts(&AudioStream::default()));
+
+    // Recording only counts when asked for
+    let recording = AudioStream {
+        capture: true,
+        sink: None,
+        ..playing("Zoom")
+    };
+    assert!(!filter.counts(&recording));
+    assert!(filter.with_capture(true).counts(&recording));
+}
+
+#[test]
+fn applications() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let filter = AudioFilter::default()
+        .with_ignored_application("Dunst")
+        .with_ignored_application("fire");
+    assert!(!filter.counts(&playing("dunst notifications")));
+    assert!(filter.counts(&AudioStream {
+        binary: Some("mpv"),
+        ..playing("mpv")
+    }));
+
+    // Either the name or the binary can match
+    assert!(!filter.counts(&playing("Web Content")));
+    assert!(!filter.counts(&AudioStream {
+        application: None,
+        ..playing("")
+    }));
+}
+
+#[test]
+fn muted() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let filter = AudioFilter::default().with_ignore_muted(true);
+    let stream = AudioStream {
+        binary: None,
+        ..playing("mpv")
+    };
+    assert!(filter.counts(&stream));
+    assert!(!filter.counts(&AudioStream {
+        muted: true,
+        ..stream
+    }));
+}
+
+#[test]
+fn sinks() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let filter = AudioFilter::default()
+        .with_sink("alsa_output.hdmi")
+        .with_capture(true);
+    let stream = AudioStream {
+        binary: None,
+        ..playing("mpv")
+    };
+    assert!(!filter.counts(&stream));
+    assert!(filter.counts(&AudioStream {
+        sink: Some("alsa_output.hdmi"),
+        ..stream
+    }));
+    assert!(!filter.counts(&AudioStream {
+        sink: None,
+        ..stream
+    }));
+
+    // Recording streams aren't played to a sink
+    assert!(filter.counts(&AudioStream {
+        sink: None,
+        capture: true,
+        ..stream
+    }));
+}
+
+#[test]
+fn capture_only() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let filter = AudioFilter::default().with_capture_only(true);
+    assert!(filter.capture());
+    assert!(!filter.playback());
+
+    let recording = AudioStream {
+        capture: true,
+        sink: None,
+        ..playing("Zoom")
+    };
+    assert!(filter.counts(&recording));
+    assert!(!filter.counts(&playing("Zoom")));
+
+    // Also counting recording streams doesn't bring playback back
+    let filter = filter.with_capture(true);
+    assert!(filter.counts(&recording));
+    assert!(!filter.counts(&playing("Zoom")));
+}
+
+#[test]
+fn monitors() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Recording what's being played, such as for a visualizer, isn't
+    // a microphone in use
+    let visualizer = AudioStream {
+        capture: true,
+        monitor: true,
+        sink: None,
+        ..playing("cava")
+    };
+    for filter in &[
+        AudioFilter::default().with_capture(true),
+        AudioFilter::default().with_capture_only(true),
+    ] {
+        assert!(!filter.counts(&visualizer));
+        assert!(filter.counts(&AudioStream {
+            monitor: false,
+            ..visualizer
+        }));
+    }
+}
@@ -0,0 +1,45 @@
+use std::{env, fs, os::unix::fs::symlink, path::Path, process};
+
+use xidlehook_core::{modules::NotWhenCamera, Module, Progress, TimerInfo};
+
+/// Create a fake process which holds these files open
+fn fake_process(proc_dir: &Path, pid: &str, files: &[&str]) {
+    let fd_dir = proc_dir.join(pid).join("fd");
+    fs::create_dir_all(&fd_dir).unwrap();
+    for (fd, file) in files.iter().enumerate() {
+        symlink(file, fd_dir.join(fd.to_string())).unwrap();
+    }
+}
+
+#[test]
+fn fake_proc() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let dir = env::temp_dir().join(format!("xidlehook-camera-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    fake_process(&dir, "1", &["/dev/null", "/dev/input/event0"]);
+    fake_process(&dir, "20", &["/dev/null", "socket:[1234]"]);
+    // Not processes, and files that only look like cameras
+    fake_process(&dir, "self", &["/dev/video0"]);
+    fake_process(&dir, "21", &["/tmp/dev/video0", "/dev/v4l/video0"]);
+    // A process that can't be looked into
+    fs::create_dir_all(dir.join("22")).unwrap();
+
+    let mut camera = NotWhenCamera::from_proc_dir(&dir);
+    let timer = TimerInfo {
+        index: 0,
+        length: 1,
+    };
+    assert!(camera.users().unwrap().is_empty());
+    assert_eq!(camera.pre_timer(timer).unwrap(), Progress::Continue);
+
+    fake_process(&dir, "300", &["/dev/null", "/dev/video2"]);
+    fake_process(&dir, "4000", &["/dev/video0"]);
+    assert_eq!(camera.users().unwrap(), vec![300, 4000]);
+    assert_eq!(camera.pre_timer(timer).unwrap(), Progress::Reset);
+
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(NotWhenCamera::from_proc_dir(&dir).users().is_err());
+}
@@ -0,0 +1,120 @@
+use std::{cell::Cell, time::Duration};
+use xidlehook_core::{
+    modules::StopAt, timers::CallbackTimer, Action::*, Chains, Progress, Xidlehook,
+};
+
+const TEST_UNIT: Duration = Duration::from_millis(50);
+
+#[test]
+fn independent_chains() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let triggered = Cell::new(0);
+
+    let mut chains = Chains::new()
+        .with_chain(
+            "lock",
+            Xidlehook::new(vec![
+                CallbackTimer::new(TEST_UNIT * 60, || triggered.set(triggered.get() | 1)),
+                CallbackTimer::new(TEST_UNIT * 10, || triggered.set(triggered.get() | 1 << 1)),
+            ]),
+        )
+        .with_chain(
+            "music",
+            Xidlehook::new(vec![CallbackTimer::new(TEST_UNIT * 100, || {
+                triggered.set(triggered.get() | 1 << 2)
+            })]),
+        );
+    assert_eq!(chains.names().collect::<Vec<_>>(), ["lock", "music"]);
+
+    // The chains count the same idle time
+    assert_eq!(
+        chains.poll(Duration::default()).unwrap(),
+        Sleep(TEST_UNIT * 60)
+    );
+    assert_eq!(chains.poll(TEST_UNIT * 60).unwrap(), Sleep(TEST_UNIT * 10));
+    assert_eq!(triggered.get(), 0b001);
+    assert_eq!(chains.poll(TEST_UNIT * 70).unwrap(), Sleep(TEST_UNIT * 30));
+    assert_eq!(triggered.get(), 0b011);
+    assert_eq!(chains.poll(TEST_UNIT * 100).unwrap(), Sleep(TEST_UNIT * 60));
+    assert_eq!(triggered.get(), 0b111);
+
+    // Each chain has its own position
+    assert_eq!(chains.get("lock").unwrap().next_index(), 2);
+    assert_eq!(chains.get("music").unwrap().next_index(), 1);
+
+    // Aborting one chain leaves the other one alone
+    triggered.set(0);
+    assert_eq!(
+        chains.poll(Duration::default()).unwrap(),
+        Sleep(TEST_UNIT * 60)
+    );
+    chains.get_mut("lock").unwrap().abort().unwrap();
+    assert_eq!(chains.poll(TEST_UNIT * 100).unwrap(), Sleep(TEST_UNIT * 60));
+    assert_eq!(triggered.get(), 0b100);
+    assert!(chains.get("lock").unwrap().aborted());
+    assert!(!chains.get("music").unwrap().aborted());
+
+    // Becoming active starts all chains over
+    triggered.set(0);
+    assert_eq!(
+        chains.poll(Duration::default()).unwrap(),
+        Sleep(TEST_UNIT * 60)
+    );
+    assert_eq!(chains.poll(TEST_UNIT * 60).unwrap(), Sleep(TEST_UNIT * 10));
+    assert_eq!(triggered.get(), 0b001);
+}
+
+#[test]
+fn chain_actions() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Without chains, or once they're done, there's nothing to do
+    let mut chains = Chains::<CallbackTimer<_>, ()>::new();
+    assert!(chains.is_empty());
+    assert_eq!(chains.poll(Duration::default()).unwrap(), Forever);
+
+    chains.insert(
+        "once",
+        Xidlehook::new(vec![CallbackTimer::new(TEST_UNIT * 10, || ())]),
+    );
+    chains.get_mut("once").unwrap().abort().unwrap();
+    assert_eq!(chains.poll(TEST_UNIT * 20).unwrap(), Sleep(TEST_UNIT * 10));
+
+    // Replacing a chain keeps its place
+    let replaced = chains.insert(
+        "once",
+        Xidlehook::new(vec![CallbackTimer::new(TEST_UNIT * 20, || ())]),
+    );
+    assert!(replaced.unwrap().aborted());
+    assert_eq!(chains.len(), 1);
+    assert_eq!(chains.poll(TEST_UNIT * 5).unwrap(), Sleep(TEST_UNIT * 15));
+    assert!(chains.remove("once").is_some());
+    assert!(chains.remove("once").is_none());
+
+    // One chain quitting quits all of them
+    let mut chains = Chains::new()
+        .with_chain(
+            "forever",
+            Xidlehook::new(vec![CallbackTimer::new(TEST_UNIT * 100, || ())])
+                .with_module(StopAt::completion()),
+        )
+        .with_chain(
+            "quick",
+            Xidlehook::new(vec![CallbackTimer::new(TEST_UNIT * 10, || ())])
+                .with_module(StopAt::completion()),
+        );
+    assert_eq!(
+        chains.poll(Duration::default()).unwrap(),
+        Sleep(TEST_UNIT * 10)
+    );
+    assert_eq!(chains.poll(TEST_UNIT * 10).unwrap(), Quit);
+    assert_eq!(
+        chains
+            .get_mut("forever")
+            .unwrap()
+            .trigger(0, TEST_UNIT * 10, true)
+            .unwrap(),
+        Progress::Stop
+    );
+}
@@ -0,0 +1,126 @@
+use std::{
+    env,
+    io::{self, BufRead, BufReader},
+    process::{Child, Command, Stdio},
+};
+
+use xidlehook_core::modules::{xcb::Corner, Xcb};
+
+#[test]
+fn parse() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    for &name in &["top-left", "top-right", "bottom-left", "bottom-right"] {
+        let corner: Corner = name.parse().unwrap();
+        assert_eq!(corner.to_string(), name);
+    }
+    assert!("".parse::<Corner>().is_err());
+    assert!("top".parse::<Corner>().is_err());
+}
+
+#[test]
+fn at() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let at = |x, y| Corner::at(x, y, 1920, 1080, 10);
+    assert_eq!(at(0, 0), Some(Corner::TopLeft));
+    assert_eq!(at(9, 9), Some(Corner::TopLeft));
+    assert_eq!(at(1919, 0), Some(Corner::TopRight));
+    assert_eq!(at(1910, 9), Some(Corner::TopRight));
+    assert_eq!(at(0, 1079), Some(Corner::BottomLeft));
+    assert_eq!(at(1919, 1079), Some(Corner::BottomRight));
+
+    // Edges, but not corners
+    assert_eq!(at(10, 0), None);
+    assert_eq!(at(0, 10), None);
+    assert_eq!(at(960, 1079), None);
+    assert_eq!(at(960, 540), None);
+
+    // Outside of the screen
+    assert_eq!(at(-1, 0), None);
+    assert_eq!(at(1920, 1079), None);
+
+    assert_eq!(Corner::at(0, 0, 1920, 1080, 0), None);
+}
+
+/// A private X server, killed when dropped
+struct Xvfb(Child);
+impl Xvfb {
+    /// Start Xvfb and point `DISPLAY` at it, or return `None` if it
+    /// isn't installed
+    fn start(width: u16, height: u16) -> Option<Self> {
+        let child = Command::new("Xvfb")
+            .args(["-displayfd", "1", "-nolisten", "tcp", "-screen", "0"])
+            .arg(format!("{}x{}x24", width, height))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+            Err(err) => panic!("failed to start Xvfb: {}", err),
+        };
+
+        // Xvfb prints the display number once it's ready
+        let mut display = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut display)
+            .unwrap();
+        assert!(!display.trim().is_empty(), "Xvfb didn't start");
+        env::set_var("DISPLAY", format!(":{}", display.trim()));
+        Some(Xvfb(child))
+    }
+}
+impl Drop for Xvfb {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn warp_pointer() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let (width, height) = (1280, 1024);
+    let _xvfb = match Xvfb::start(width, height) {
+        Some(xvfb) => xvfb,
+        None => {
+            eprintln!("Xvfb isn't installed, skipping");
+            return;
+        },
+    };
+
+    let xcb = Xcb::new().unwrap();
+    let (conn, screen) = xcb::Connection::connect(None).unwrap();
+    let root = conn
+        .get_setup()
+        .roots()
+        .nth(screen as usize)
+        .unwrap()
+        .root();
+    let warp = |x: u16, y: u16| {
+        xcb::xproto::warp_pointer_checked(&conn, xcb::NONE, root, 0, 0, 0, 0, x as i16, y as i16)
+            .request_check()
+            .unwrap();
+    };
+
+    // Xvfb has a single output covering the whole screen
+    let outputs = xcb.outputs().unwrap();
+    assert_eq!(outputs.len(), 1);
+    assert_eq!((outputs[0].width, outputs[0].height), (width, height));
+
+    warp(0, 0);
+    assert_eq!(xcb.pointer_corner(10).unwrap(), Some(Corner::TopLeft));
+    warp(width - 1, 0);
+    assert_eq!(xcb.pointer_corner(10).unwrap(), Some(Corner::TopRight));
+    warp(0, height - 1);
+    assert_eq!(xcb.pointer_corner(10).unwrap(), Some(Corner::BottomLeft));
+    warp(width - 1, height - 1);
+    assert_eq!(xcb.pointer_corner(10).unwrap(), Some(Corner::BottomRight));
+    warp(width - 20, height - 1);
+    assert_eq!(xcb.pointer_corner(10).unwrap(), None);
+    assert_eq!(xcb.pointer_corner(30).unwrap(), Some(Corner::BottomRight));
+    warp(width / 2, height / 2);
+    assert_eq!(xcb.pointer_corner(10).unwrap(), None);
+}
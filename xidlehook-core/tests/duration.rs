@@ -0,0 +1,76 @@
+use std::time::Duration;
+use xidlehook_core::duration;
+
+#[test]
+fn parse() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Backwards compatible with the old number of seconds
+    assert_eq!(duration::parse("270").unwrap(), Duration::from_secs(270));
+
+    assert_eq!(
+        duration::parse("500ms").unwrap(),
+        Duration::from_millis(500)
+    );
+    assert_eq!(duration::parse("90s").unwrap(), Duration::from_secs(90));
+    assert_eq!(duration::parse("5m").unwrap(), Duration::from_secs(5 * 60));
+    assert_eq!(duration::parse("4m30s").unwrap(), Duration::from_secs(270));
+    assert_eq!(
+        duration::parse("1h30m").unwrap(),
+        Duration::from_secs(90 * 60)
+    );
+    assert_eq!(
+        duration::parse(" 1d 2h ").unwrap(),
+        Duration::from_secs(26 * 60 * 60)
+    );
+    assert_eq!(
+        duration::parse("1s500ms").unwrap(),
+        Duration::from_millis(1500)
+    );
+
+    assert!(duration::parse("").is_err());
+    assert!(duration::parse("m").is_err());
+    assert!(duration::parse("1h30").is_err());
+    assert!(duration::parse("5 minutes").is_err());
+    assert!(duration::parse("-5s").is_err());
+    assert!(duration::parse("99999999999999999999h").is_err());
+    assert!(duration::parse("9999999999999999999999999999999999999999d").is_err());
+
+    assert_eq!(
+        duration::parse("1ms250us").unwrap(),
+        Duration::from_micros(1250)
+    );
+    assert_eq!(duration::parse("10ns").unwrap(), Duration::from_nanos(10));
+}
+
+#[test]
+fn format() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    assert_eq!(duration::format(Duration::default()), "0s");
+    assert_eq!(duration::format(Duration::from_millis(500)), "500ms");
+    assert_eq!(duration::format(Duration::from_secs(270)), "4m30s");
+    assert_eq!(duration::format(Duration::from_secs(90 * 60)), "1h30m");
+    assert_eq!(duration::format(Duration::from_millis(1500)), "1s500ms");
+    assert_eq!(
+        duration::format(Duration::from_nanos(1_000_250_001)),
+        "1s250us1ns"
+    );
+
+    for &secs in &[1, 59, 60, 3599, 3600, 86_400, 90_061] {
+        let duration = Duration::from_secs(secs);
+        assert_eq!(
+            duration::parse(&duration::format(duration)).unwrap(),
+            duration
+        );
+    }
+    for &nanos in &[1, 999, 1_000_001, 1_500_000_007, 3_600_000_000_001] {
+        let duration = Duration::from_nanos(nanos);
+        assert_eq!(
+            duration::parse(&duration::format(duration)).unwrap(),
+            duration
+        );
+    }
+    let max = Duration::new(u64::MAX, 999_999_999);
+    assert_eq!(duration::parse(&duration::format(max)).unwrap(), max);
+}
@@ -0,0 +1,116 @@
+use std::{
+    env, fs,
+    fs::OpenOptions,
+    io::Write,
+    mem,
+    path::Path,
+    process, slice, thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use nix::libc;
+use xidlehook_core::{modules::Evdev, IdleSource};
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const KEY_A: u16 = 30;
+
+/// Append a fake input event that happened `ago` in the past
+fn write_event(path: &Path, type_: u16, ago: Duration) {
+    let since_epoch = (SystemTime::now() - ago)
+        .duration_since(UNIX_EPOCH)
+        .unwrap();
+    let event = libc::input_event {
+        time: libc::timeval {
+            tv_sec: since_epoch.as_secs() as libc::time_t,
+            tv_usec: since_epoch.subsec_micros() as libc::suseconds_t,
+        },
+        type_,
+        code: KEY_A,
+        value: 1,
+    };
+    let bytes = unsafe {
+        slice::from_raw_parts(
+            &event as *const libc::input_event as *const u8,
+            mem::size_of::<libc::input_event>(),
+        )
+    };
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap()
+        .write_all(bytes)
+        .unwrap();
+}
+
+const TEST_UNIT: Duration = Duration::from_millis(100);
+
+fn assert_about(idle: Duration, expected: Duration) {
+    assert!(
+        idle >= expected && idle < expected + TEST_UNIT,
+        "idle time {:?} is not about {:?}",
+        idle,
+        expected
+    );
+}
+
+#[test]
+fn fake_devices() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let dir = env::temp_dir().join(format!("xidlehook-evdev-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let keyboard = dir.join("event0");
+    write_event(&keyboard, EV_KEY, Duration::from_secs(60));
+    // Not an event device, must be ignored
+    write_event(&dir.join("mouse0"), EV_KEY, Duration::from_secs(0));
+
+    let evdev = Evdev::from_dir(&dir).unwrap();
+
+    // Devices are only read once the idle time is requested, but the
+    // events remember when they happened. Input from before the source
+    // was created doesn't count, however.
+    write_event(&keyboard, EV_KEY, Duration::from_secs(30));
+    assert_about(evdev.get_idle().unwrap(), Duration::from_secs(0));
+
+    // Synchronization events aren't user input
+    write_event(&keyboard, EV_SYN, Duration::from_secs(0));
+    assert_about(evdev.get_idle().unwrap(), Duration::from_secs(0));
+
+    // Devices plugged in later are picked up
+    let mouse = dir.join("event1");
+    write_event(&mouse, EV_KEY, Duration::from_secs(0));
+    assert_about(evdev.get_idle().unwrap(), Duration::from_secs(0));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn idle_from_event_timestamps() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let dir = env::temp_dir().join(format!("xidlehook-evdev-paths-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let device = dir.join("keyboard");
+    fs::write(&device, b"").unwrap();
+
+    let evdev = Evdev::from_paths(vec![&device]).unwrap();
+    thread::sleep(TEST_UNIT * 3);
+
+    // The idle time is counted from when the event happened, not from when it was read
+    write_event(&device, EV_KEY, TEST_UNIT * 2);
+    assert_about(evdev.get_idle().unwrap(), TEST_UNIT * 2);
+
+    // Older events never move the last input backwards
+    write_event(&device, EV_KEY, Duration::from_secs(3600));
+    assert_about(evdev.get_idle().unwrap(), TEST_UNIT * 2);
+
+    assert!(Evdev::from_paths(vec![dir.join("missing")]).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
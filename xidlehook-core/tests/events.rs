@@ -0,0 +1,100 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+use xidlehook_core::{
+    timers::CallbackTimer, Action::*, Event, Module, Progress, Result, TimerInfo, Xidlehook,
+};
+
+const TEST_UNIT: Duration = Duration::from_millis(50);
+
+/// A module that records all events
+#[derive(Default)]
+struct Recorder(Rc<RefCell<Vec<Event>>>);
+impl Module for Recorder {
+    fn event(&mut self, event: Event) -> Result<()> {
+        self.0.borrow_mut().push(event);
+        Ok(())
+    }
+}
+
+fn info(index: usize) -> TimerInfo {
+    TimerInfo { index, length: 2 }
+}
+
+#[test]
+fn events() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+
+    let mut xidlehook = Xidlehook::new(vec![
+        CallbackTimer::new(TEST_UNIT * 10, || ()),
+        CallbackTimer::new(TEST_UNIT * 10, || ()),
+    ])
+    .register(Recorder(Rc::clone(&events)));
+
+    // Nothing happens while the user is active
+    assert_eq!(xidlehook.poll(TEST_UNIT * 5).unwrap(), Sleep(TEST_UNIT * 5));
+    assert_eq!(xidlehook.poll(TEST_UNIT * 1).unwrap(), Sleep(TEST_UNIT * 9));
+    assert_eq!(*events.borrow(), []);
+
+    xidlehook.poll(TEST_UNIT * 10).unwrap();
+    assert_eq!(
+        events.borrow_mut().split_off(0),
+        [Event::Idle, Event::Activated(info(0))]
+    );
+
+    xidlehook.poll(TEST_UNIT * 20).unwrap();
+    assert_eq!(
+        events.borrow_mut().split_off(0),
+        [Event::Activated(info(1)), Event::Deactivated(info(0))]
+    );
+
+    // The user comes back
+    xidlehook.poll(Duration::from_millis(0)).unwrap();
+    assert_eq!(
+        events.borrow_mut().split_off(0),
+        [Event::Active, Event::Aborted(info(1)), Event::Reset]
+    );
+
+    // Aborting twice only counts once, and resetting an unstarted chain does nothing
+    xidlehook.poll(TEST_UNIT * 10).unwrap();
+    xidlehook.abort().unwrap();
+    xidlehook.abort().unwrap();
+    assert_eq!(
+        events.borrow_mut().split_off(0),
+        [
+            Event::Idle,
+            Event::Activated(info(0)),
+            Event::Aborted(info(0))
+        ]
+    );
+    xidlehook.reset(TEST_UNIT * 10).unwrap();
+    xidlehook.reset(TEST_UNIT * 10).unwrap();
+    assert_eq!(events.borrow_mut().split_off(0), [Event::Reset]);
+}
+
+/// A module that holds back every timer
+struct Blocker;
+impl Module for Blocker {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        Ok(Progress::Abort)
+    }
+}
+
+#[test]
+fn blocked_first_timer() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+
+    let mut xidlehook = Xidlehook::new(vec![CallbackTimer::new(TEST_UNIT * 10, || ())])
+        .register((Blocker, Recorder(Rc::clone(&events))));
+
+    xidlehook.poll(TEST_UNIT * 10).unwrap();
+    assert!(xidlehook.aborted());
+    assert_eq!(*events.borrow(), []);
+
+    // The chain starting over is the only sign of the user coming back
+    xidlehook.poll(Duration::from_millis(0)).unwrap();
+    assert!(!xidlehook.aborted());
+    assert_eq!(events.borrow_mut().split_off(0), [Event::Reset]);
+}
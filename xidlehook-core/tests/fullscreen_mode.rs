@@ -0,0 +1,23 @@
+use xidlehook_core::modules::xcb::FullscreenMode;
+
+#[test]
+fn parse() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    assert_eq!(FullscreenMode::default(), FullscreenMode::Any);
+    assert_eq!(
+        "any".parse::<FullscreenMode>().unwrap(),
+        FullscreenMode::Any
+    );
+    assert_eq!(
+        "visible".parse::<FullscreenMode>().unwrap(),
+        FullscreenMode::Visible
+    );
+    assert_eq!(
+        "focused".parse::<FullscreenMode>().unwrap(),
+        FullscreenMode::Focused
+    );
+
+    assert!("Focused".parse::<FullscreenMode>().is_err());
+    assert!("".parse::<FullscreenMode>().is_err());
+}
@@ -66,3 +66,68 @@ fn general_timer_test() {
     assert_eq!(timer.poll(TEST_UNIT * 101).unwrap(), Sleep(TEST_UNIT * 10));
     assert_eq!(triggered.get(), 0b0001);
 }
+
+#[test]
+fn chain_state() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let mut timer = Xidlehook::new(vec![
+        CallbackTimer::new(TEST_UNIT * 10, || ()),
+        CallbackTimer::new(TEST_UNIT * 20, || ()),
+    ]);
+
+    assert_eq!(timer.next_index(), 0);
+    assert_eq!(timer.time_left(TEST_UNIT * 4).unwrap(), Some(TEST_UNIT * 6));
+
+    timer.poll(TEST_UNIT * 10).unwrap();
+    assert_eq!(timer.next_index(), 1);
+    assert_eq!(
+        timer.time_left(TEST_UNIT * 15).unwrap(),
+        Some(TEST_UNIT * 15)
+    );
+
+    // Changing a single timer leaves the chain running
+    timer.timer_mut(1).unwrap().disabled = true;
+    timer.timer_mut(1).unwrap().disabled = false;
+    assert!(timer.timer_mut(2).is_none());
+    assert!(!timer.aborted());
+    assert_eq!(timer.next_index(), 1);
+
+    timer.poll(TEST_UNIT * 30).unwrap();
+    assert_eq!(timer.next_index(), 2);
+    assert_eq!(timer.time_left(TEST_UNIT * 30).unwrap(), None);
+
+    // Any activity means starting over
+    assert_eq!(timer.time_left(TEST_UNIT * 1).unwrap(), Some(TEST_UNIT * 9));
+
+    timer.poll(TEST_UNIT * 1).unwrap();
+    timer.abort().unwrap();
+    assert!(timer.aborted());
+    assert_eq!(timer.next_index(), 0);
+    assert_eq!(timer.time_left(TEST_UNIT * 2).unwrap(), None);
+}
+
+#[test]
+fn replace_next_timers() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let triggered = Cell::new(0);
+    let timer = |time, bit: u32| {
+        let triggered = &triggered;
+        CallbackTimer::new(TEST_UNIT * time, move || {
+            triggered.set(triggered.get() | 1 << bit)
+        })
+    };
+
+    let mut chain = Xidlehook::new(vec![timer(100, 0), timer(50, 1)]);
+    assert_eq!(chain.poll(TEST_UNIT * 0).unwrap(), Sleep(TEST_UNIT * 100));
+    assert_eq!(chain.poll(TEST_UNIT * 100).unwrap(), Sleep(TEST_UNIT * 50));
+    assert_eq!(triggered.get(), 0b01);
+
+    // The first timer stays activated, and the idle time still counts
+    chain.replace_next_timers(vec![timer(100, 2), timer(20, 3), timer(50, 4)]);
+    assert_eq!(chain.next_index(), 1);
+    assert_eq!(chain.poll(TEST_UNIT * 110).unwrap(), Sleep(TEST_UNIT * 10));
+    assert_eq!(chain.poll(TEST_UNIT * 120).unwrap(), Sleep(TEST_UNIT * 50));
+    assert_eq!(triggered.get(), 0b1001);
+}
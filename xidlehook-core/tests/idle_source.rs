@@ -0,0 +1,184 @@
+use std::{
+    cell::{Cell, RefCell},
+    io::{Read, Write},
+    os::unix::{
+        io::{AsRawFd, RawFd},
+        net::UnixStream,
+    },
+    time::{Duration, Instant},
+};
+use xidlehook_core::{modules::StopAt, timers::CallbackTimer, IdleSource, Result, Xidlehook};
+
+const TEST_UNIT: Duration = Duration::from_millis(5);
+
+/// An idle source that pretends the user is idle for one more test
+/// unit each time it's asked
+struct FakeSource {
+    idle: Cell<Duration>,
+}
+impl IdleSource for FakeSource {
+    fn get_idle(&self) -> Result<Duration> {
+        let idle = self.idle.get() + TEST_UNIT;
+        self.idle.set(idle);
+        Ok(idle)
+    }
+}
+
+struct FixedSource(Duration);
+impl IdleSource for FixedSource {
+    fn get_idle(&self) -> Result<Duration> {
+        Ok(self.0)
+    }
+}
+
+/// An idle source that wakes up the main loop right away and jumps
+/// ahead by a fixed idle time
+struct WakingSource {
+    idle: Cell<Duration>,
+    target: Duration,
+    wakeups: Cell<u32>,
+    /// Whether each wakeup asked for activity
+    activity: RefCell<Vec<bool>>,
+    socket: (UnixStream, UnixStream),
+}
+impl IdleSource for WakingSource {
+    fn get_idle(&self) -> Result<Duration> {
+        Ok(self.idle.get())
+    }
+    fn arm(&self, _idle: Duration, _delay: Duration, activity: bool) -> Result<Vec<RawFd>> {
+        self.activity.borrow_mut().push(activity);
+        (&self.socket.0).write_all(&[0])?;
+        Ok(vec![self.socket.1.as_raw_fd()])
+    }
+    fn disarm(&self) -> Result<()> {
+        (&self.socket.1).read_exact(&mut [0])?;
+        self.wakeups.set(self.wakeups.get() + 1);
+        self.idle.set(self.idle.get() + self.target);
+        Ok(())
+    }
+    fn wakes_on_activity(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn composite_sources() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let pair = (FixedSource(TEST_UNIT * 3), FixedSource(TEST_UNIT * 2));
+    assert_eq!(pair.get_idle().unwrap(), TEST_UNIT * 2);
+
+    let list = vec![
+        FixedSource(TEST_UNIT * 7),
+        FixedSource(TEST_UNIT * 5),
+        FixedSource(TEST_UNIT * 9),
+    ];
+    assert_eq!(list.get_idle().unwrap(), TEST_UNIT * 5);
+    assert_eq!(
+        Vec::<FixedSource>::new().get_idle().unwrap(),
+        Duration::default()
+    );
+
+    let boxed: Box<dyn IdleSource> = Box::new(pair);
+    assert_eq!(boxed.get_idle().unwrap(), TEST_UNIT * 2);
+}
+
+impl WakingSource {
+    fn new(target: Duration) -> Self {
+        Self {
+            idle: Cell::new(Duration::default()),
+            target,
+            wakeups: Cell::new(0),
+            activity: RefCell::default(),
+            socket: UnixStream::pair().unwrap(),
+        }
+    }
+}
+
+#[test]
+fn composite_sources_wake_up_early() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let delay = Duration::from_secs(3600);
+
+    // Only wakes up on activity if all sources do
+    let mixed = (WakingSource::new(delay), FixedSource(TEST_UNIT));
+    assert!(!mixed.wakes_on_activity());
+    assert_eq!(mixed.arm(TEST_UNIT, delay, true).unwrap().len(), 1);
+    mixed.disarm().unwrap();
+    assert_eq!(mixed.0.wakeups.get(), 1);
+
+    let pair = (WakingSource::new(delay), WakingSource::new(delay));
+    assert!(pair.wakes_on_activity());
+    let start = Instant::now();
+    Xidlehook::new(vec![CallbackTimer::new(delay, || ())])
+        .register(StopAt::completion())
+        .main_sync(&pair, || false)
+        .unwrap();
+    assert!(start.elapsed() < TEST_UNIT * 100);
+    assert_eq!((pair.0.wakeups.get(), pair.1.wakeups.get()), (1, 1));
+
+    let list = vec![WakingSource::new(delay), WakingSource::new(delay)];
+    assert!(list.wakes_on_activity());
+    assert!(!Vec::<WakingSource>::new().wakes_on_activity());
+    assert_eq!(list.arm(TEST_UNIT, delay, true).unwrap().len(), 2);
+    list.disarm().unwrap();
+    assert!(list.iter().all(|source| source.wakeups.get() == 1));
+}
+
+#[test]
+fn main_sync_with_fake_source() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let triggered = Cell::new(0);
+
+    let xidlehook = Xidlehook::new(vec![
+        CallbackTimer::new(TEST_UNIT, || triggered.set(triggered.get() | 1)),
+        CallbackTimer::new(TEST_UNIT, || triggered.set(triggered.get() | 1 << 1)),
+    ])
+    .register(StopAt::completion());
+
+    let source = FakeSource {
+        idle: Cell::new(Duration::default()),
+    };
+    xidlehook.main_sync(&source, || false).unwrap();
+
+    assert_eq!(triggered.get(), 0b11);
+}
+
+#[test]
+fn main_sync_wakes_up_early() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let triggered = Cell::new(false);
+
+    let delay = Duration::from_secs(3600);
+    let xidlehook = Xidlehook::new(vec![CallbackTimer::new(delay, || triggered.set(true))])
+        .register(StopAt::completion());
+
+    let source = WakingSource::new(delay);
+    let start = Instant::now();
+    xidlehook.main_sync(&source, || false).unwrap();
+
+    assert!(start.elapsed() < TEST_UNIT * 100);
+    assert_eq!(source.wakeups.get(), 1);
+    assert!(triggered.get());
+}
+
+#[test]
+fn activity_only_while_a_timer_is_active() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Wakes up at the first timer, then waits for the second one
+    let delay = Duration::from_secs(3600);
+    let source = WakingSource::new(delay);
+    Xidlehook::new(vec![
+        CallbackTimer::new(delay, || ()),
+        CallbackTimer::new(delay, || ()),
+    ])
+    .register(StopAt::completion())
+    .main_sync(&source, || false)
+    .unwrap();
+
+    assert_eq!(*source.activity.borrow(), [false, true]);
+}
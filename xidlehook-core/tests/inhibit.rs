@@ -0,0 +1,62 @@
+use std::{cell::Cell, thread, time::Duration};
+use xidlehook_core::{modules::Inhibit, timers::CallbackTimer, Action::*, Xidlehook};
+
+const TEST_UNIT: Duration = Duration::from_millis(50);
+
+#[test]
+fn inhibit() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let triggered = Cell::new(false);
+    let inhibit = Inhibit::new();
+
+    let mut timer = Xidlehook::new(vec![CallbackTimer::new(TEST_UNIT * 10, || {
+        triggered.set(true)
+    })])
+    .register(inhibit.clone());
+
+    let cookie = inhibit.inhibit("Watching a movie".into(), "mpv".into(), None);
+    assert!(inhibit.is_inhibited());
+
+    // The chain starts over instead of activating the timer
+    assert_eq!(timer.poll(TEST_UNIT * 10).unwrap(), Sleep(TEST_UNIT * 10));
+    assert!(!triggered.get());
+
+    // User activity doesn't release inhibitors
+    timer.poll(Duration::from_millis(0)).unwrap();
+    assert_eq!(timer.poll(TEST_UNIT * 10).unwrap(), Sleep(TEST_UNIT * 10));
+    assert!(!triggered.get());
+
+    let inhibitors = inhibit.inhibitors();
+    assert_eq!(inhibitors.len(), 1);
+    assert_eq!(inhibitors[0].cookie, cookie);
+    assert_eq!(inhibitors[0].reason, "Watching a movie");
+    assert_eq!(inhibitors[0].owner, "mpv");
+
+    // Once released, the chain continues from where it started over
+    assert!(inhibit.uninhibit(cookie));
+    assert!(!inhibit.uninhibit(cookie));
+    assert_eq!(timer.poll(TEST_UNIT * 15).unwrap(), Sleep(TEST_UNIT * 5));
+    timer.poll(TEST_UNIT * 20).unwrap();
+    assert!(triggered.get());
+}
+
+#[test]
+fn inhibit_timeout() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let inhibit = Inhibit::new();
+    let first = inhibit.inhibit("Presenting".into(), "a".into(), Some(TEST_UNIT));
+    let second = inhibit.inhibit("Presenting".into(), "b".into(), Some(TEST_UNIT * 100));
+    assert_ne!(first, second);
+    assert_eq!(inhibit.inhibitors().len(), 2);
+
+    thread::sleep(TEST_UNIT * 2);
+    let inhibitors = inhibit.inhibitors();
+    assert_eq!(inhibitors.len(), 1);
+    assert_eq!(inhibitors[0].cookie, second);
+    assert!(!inhibit.uninhibit(first));
+
+    assert!(inhibit.uninhibit(second));
+    assert!(!inhibit.is_inhibited());
+}
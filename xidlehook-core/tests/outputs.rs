@@ -0,0 +1,63 @@
+use xidlehook_core::modules::xcb::{Corner, Output, OutputSelector};
+
+fn output(name: &str, primary: bool) -> Output {
+    Output {
+        name: name.into(),
+        primary,
+        root: 1,
+        x: 1920,
+        y: 0,
+        width: 2560,
+        height: 1440,
+    }
+}
+
+#[test]
+fn selectors() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let primary: OutputSelector = "primary".parse().unwrap();
+    assert_eq!(primary, OutputSelector::Primary);
+    assert!(primary.matches(&output("DP-1", true)));
+    assert!(!primary.matches(&output("DP-1", false)));
+
+    let hdmi: OutputSelector = "HDMI-1".parse().unwrap();
+    assert_eq!(hdmi.to_string(), "HDMI-1");
+    assert!(hdmi.matches(&output("HDMI-1", false)));
+    assert!(!hdmi.matches(&output("HDMI-2", true)));
+
+    assert!("".parse::<OutputSelector>().is_err());
+}
+
+#[test]
+fn contains() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let output = output("DP-1", false);
+    assert!(output.contains(1920, 0));
+    assert!(output.contains(1920 + 1280, 720));
+    assert!(!output.contains(1919, 720));
+    assert!(!output.contains(1920 + 2560, 720));
+    assert!(!output.contains(1920, 1440));
+    assert!(!output.contains(1920, -1));
+}
+
+#[test]
+fn corners() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Right of a 1920x1080 monitor
+    let output = output("DP-1", false);
+    assert_eq!(output.corner(1920, 0, 10), Some(Corner::TopLeft));
+    assert_eq!(output.corner(1920 + 2559, 0, 10), Some(Corner::TopRight));
+    assert_eq!(output.corner(1920, 1439, 10), Some(Corner::BottomLeft));
+    assert_eq!(
+        output.corner(1920 + 2559, 1439, 10),
+        Some(Corner::BottomRight)
+    );
+
+    // Only its own corners count, not those of the monitor next to it
+    assert_eq!(output.corner(1919, 0, 10), None);
+    assert_eq!(output.corner(1920 + 5, 5, 10), Some(Corner::TopLeft));
+    assert_eq!(output.corner(2560, 720, 10), None);
+}
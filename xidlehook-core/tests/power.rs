@@ -0,0 +1,84 @@
+use std::{env, fs, path::Path, process};
+
+use xidlehook_core::modules::{power::PowerStatus, PowerSupply};
+
+/// Create a fake power supply with these attributes
+fn fake_supply(sysfs_dir: &Path, name: &str, attrs: &[(&str, &str)]) {
+    let dir = sysfs_dir.join(name);
+    fs::create_dir_all(&dir).unwrap();
+    for (attr, value) in attrs {
+        fs::write(dir.join(attr), format!("{}\n", value)).unwrap();
+    }
+}
+
+#[test]
+fn fake_sysfs() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let dir = env::temp_dir().join(format!("xidlehook-power-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let power = PowerSupply::from_sysfs_dir(&dir);
+
+    // Desktops have no batteries
+    assert_eq!(
+        power.status().unwrap(),
+        PowerStatus {
+            ac: true,
+            capacity: None
+        }
+    );
+
+    fake_supply(&dir, "AC", &[("type", "Mains"), ("online", "0")]);
+    fake_supply(
+        &dir,
+        "BAT0",
+        &[("type", "Battery"), ("present", "1"), ("capacity", "80")],
+    );
+    fake_supply(&dir, "BAT1", &[("type", "Battery"), ("capacity", "41")]);
+    // Not a battery of the computer, or not plugged in
+    fake_supply(
+        &dir,
+        "hidpp_battery_0",
+        &[("type", "Battery"), ("scope", "Device"), ("capacity", "5")],
+    );
+    fake_supply(
+        &dir,
+        "BAT2",
+        &[("type", "Battery"), ("present", "0"), ("capacity", "0")],
+    );
+    // Not a power supply
+    fs::create_dir_all(dir.join("empty")).unwrap();
+
+    assert_eq!(
+        power.status().unwrap(),
+        PowerStatus {
+            ac: false,
+            capacity: Some(60)
+        }
+    );
+
+    fake_supply(&dir, "AC", &[("online", "1")]);
+    assert_eq!(
+        power.status().unwrap(),
+        PowerStatus {
+            ac: true,
+            capacity: Some(60)
+        }
+    );
+
+    // Chargers over USB-C count too
+    fake_supply(&dir, "AC", &[("online", "0")]);
+    fake_supply(
+        &dir,
+        "ucsi-source-psy-1",
+        &[("type", "USB"), ("online", "1")],
+    );
+    assert!(power.status().unwrap().ac);
+
+    fake_supply(&dir, "BAT0", &[("capacity", "invalid")]);
+    assert!(power.status().is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(power.status().is_err());
+}
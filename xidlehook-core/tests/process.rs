@@ -0,0 +1,134 @@
+use std::{env, fs, os::unix::fs::MetadataExt, path::Path, process};
+
+use xidlehook_core::{
+    modules::{process::ProcessPattern, NotWhenProcess},
+    Module, Progress, TimerInfo,
+};
+
+/// Create a fake process with this name and command line
+fn fake_process(proc_dir: &Path, pid: &str, name: &str, cmdline: &[&str]) {
+    let dir = proc_dir.join(pid);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("comm"), format!("{}\n", name)).unwrap();
+    let mut args = Vec::new();
+    for arg in cmdline {
+        args.extend_from_slice(arg.as_bytes());
+        args.push(0);
+    }
+    fs::write(dir.join("cmdline"), args).unwrap();
+}
+
+/// Give a fake process a parent
+fn fake_parent(proc_dir: &Path, pid: &str, name: &str, ppid: u32) {
+    let stat = format!("{} ({}) S {} 1 1 0 -1\n", pid, name, ppid);
+    fs::write(proc_dir.join(pid).join("stat"), stat).unwrap();
+}
+
+fn patterns(patterns: &[&str]) -> Vec<ProcessPattern> {
+    patterns
+        .iter()
+        .map(|pattern| pattern.parse().unwrap())
+        .collect()
+}
+
+#[test]
+fn parse() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let pattern: ProcessPattern = "^rsync$".parse().unwrap();
+    assert_eq!(pattern.to_string(), "name=^rsync$");
+    assert!(pattern.matches("rsync", "foo"));
+    assert!(!pattern.matches("rsync2", "rsync"));
+
+    let pattern: ProcessPattern = "cmdline=cargo (build|test)".parse().unwrap();
+    assert_eq!(pattern.to_string(), "cmdline=cargo (build|test)");
+    assert!(pattern.matches("cargo", "/usr/bin/cargo build --release"));
+    assert!(!pattern.matches("cargo build", "cargo check"));
+
+    assert!("".parse::<ProcessPattern>().is_err());
+    assert!("cmdline=".parse::<ProcessPattern>().is_err());
+    assert!("name=(".parse::<ProcessPattern>().is_err());
+}
+
+#[test]
+fn fake_proc() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let dir = env::temp_dir().join(format!("xidlehook-process-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    fake_process(&dir, "1", "systemd", &["/sbin/init", "splash"]);
+    fake_process(&dir, "2", "kthreadd", &[]);
+    // Not a process
+    fake_process(&dir, "self", "rsync", &["rsync"]);
+    // A process that exited while looking at it
+    fs::create_dir_all(dir.join("3")).unwrap();
+
+    let mut module =
+        NotWhenProcess::from_proc_dir(&dir, patterns(&["^rsync$", "cmdline=cargo build"]));
+    let timer = TimerInfo {
+        index: 0,
+        length: 1,
+    };
+    assert!(module.matching_processes().unwrap().is_empty());
+    assert_eq!(module.pre_timer(timer).unwrap(), Progress::Continue);
+
+    fake_process(&dir, "300", "rsync", &["rsync", "-a", "src", "dst"]);
+    fake_process(&dir, "4000", "cargo", &["cargo", "build", "--release"]);
+    fake_process(&dir, "4001", "cargo", &["cargo", "check"]);
+    assert_eq!(module.matching_processes().unwrap(), vec![300, 4000]);
+    assert_eq!(module.pre_timer(timer).unwrap(), Progress::Reset);
+
+    let mut module = module.with_progress(Progress::Abort);
+    assert_eq!(module.pre_timer(timer).unwrap(), Progress::Abort);
+
+    // All fake processes belong to whoever runs the test
+    let uid = fs::metadata(dir.join("300")).unwrap().uid();
+    let module = module.with_user(Some(uid));
+    assert_eq!(module.matching_processes().unwrap(), vec![300, 4000]);
+    let module = module.with_user(Some(uid.wrapping_add(1)));
+    assert!(module.matching_processes().unwrap().is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(module.matching_processes().is_err());
+}
+
+#[test]
+fn not_ourselves() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let dir = env::temp_dir().join(format!("xidlehook-process-self-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    // Started by a shell like `sh -c 'xidlehook ... cmdline=rsync'`,
+    // so both have the pattern in their command lines
+    let pid = process::id().to_string();
+    fake_process(
+        &dir,
+        &pid,
+        "xidlehook",
+        &["xidlehook", "--not-when-process", "cmdline=rsync"],
+    );
+    fake_parent(&dir, &pid, "xidlehook", 200);
+    fake_process(
+        &dir,
+        "200",
+        "sh",
+        &["sh", "-c", "xidlehook --not-when-process cmdline=rsync"],
+    );
+    fake_parent(&dir, "200", "sh (wrapper)", 1);
+    fake_process(&dir, "1", "systemd", &["/sbin/init", "rsync"]);
+    fake_parent(&dir, "1", "systemd", 0);
+
+    // Unrelated processes still match, shells included
+    fake_process(&dir, "300", "rsync", &["rsync", "-a", "src", "dst"]);
+    fake_parent(&dir, "300", "rsync", 200);
+    fake_process(&dir, "301", "sh", &["sh", "-c", "rsync -a src dst"]);
+
+    let module = NotWhenProcess::from_proc_dir(&dir, patterns(&["cmdline=rsync"]));
+    assert_eq!(module.matching_processes().unwrap(), vec![300, 301]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,225 @@
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use xidlehook_core::{
+    schedule::{Clock, LocalTime, Period, Schedule, Scheduled, Weekday},
+    timers::CallbackTimer,
+    Action::*,
+    Result, Xidlehook,
+};
+
+const TEST_UNIT: Duration = Duration::from_millis(50);
+
+/// A clock which shows whatever time the test sets
+#[derive(Clone, Default)]
+struct FakeClock(Rc<Cell<Option<LocalTime>>>);
+impl FakeClock {
+    fn set(&self, weekday: Weekday, hour: u8, minute: u8) {
+        self.0.set(Some(LocalTime {
+            weekday,
+            hour,
+            minute,
+            second: 0,
+        }));
+    }
+}
+impl Clock for FakeClock {
+    fn now(&self) -> Result<LocalTime> {
+        self.0.get().ok_or_else(|| "the clock is broken".into())
+    }
+}
+
+fn at(weekday: Weekday, hour: u8, minute: u8) -> LocalTime {
+    LocalTime {
+        weekday,
+        hour,
+        minute,
+        second: 0,
+    }
+}
+
+fn hours(hours: u64) -> Duration {
+    Duration::from_secs(hours * 60 * 60)
+}
+
+#[test]
+fn periods() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let work: Period = "mon-fri 09:00-18:00".parse().unwrap();
+    assert!(work.contains(at(Weekday::Monday, 9, 0)));
+    assert!(work.contains(at(Weekday::Friday, 17, 59)));
+    assert!(!work.contains(at(Weekday::Friday, 18, 0)));
+    assert!(!work.contains(at(Weekday::Tuesday, 8, 59)));
+    assert!(!work.contains(at(Weekday::Saturday, 12, 0)));
+
+    let weekend: Period = "sat,sun".parse().unwrap();
+    assert!(weekend.contains(at(Weekday::Saturday, 0, 0)));
+    assert!(weekend.contains(at(Weekday::Sunday, 23, 59)));
+    assert!(!weekend.contains(at(Weekday::Monday, 0, 0)));
+
+    // Lasts into the next day, and wraps around the week
+    let night: Period = "fri-mon 22:00-07:00".parse().unwrap();
+    assert!(night.contains(at(Weekday::Friday, 22, 0)));
+    assert!(night.contains(at(Weekday::Saturday, 6, 59)));
+    assert!(night.contains(at(Weekday::Tuesday, 3, 0)));
+    assert!(!night.contains(at(Weekday::Friday, 3, 0)));
+    assert!(!night.contains(at(Weekday::Tuesday, 22, 0)));
+    assert!(!night.contains(at(Weekday::Monday, 7, 0)));
+
+    let evening: Period = "18:00-24:00".parse().unwrap();
+    assert!(evening.contains(at(Weekday::Wednesday, 23, 59)));
+    assert!(!evening.contains(at(Weekday::Thursday, 0, 0)));
+
+    for invalid in &[
+        "",
+        "monday",
+        "mon-fri mon",
+        "09:00",
+        "09:00-18:00 10:00-11:00",
+        "25:00-26:00",
+        "9999:00-10:00",
+        "09:60-10:00",
+        "24:00-06:00",
+        "9-18",
+    ] {
+        assert!(invalid.parse::<Period>().is_err(), "{:?}", invalid);
+    }
+}
+
+#[test]
+fn schedules() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let clock = FakeClock::default();
+    let schedule = Schedule::new()
+        .with_clock(clock.clone())
+        .with_during("22:00-07:00".parse().unwrap())
+        .with_during("sat,sun".parse().unwrap())
+        .with_not_during("sun 12:00-13:00".parse().unwrap());
+
+    clock.set(Weekday::Monday, 12, 0);
+    assert!(!schedule.active());
+    clock.set(Weekday::Monday, 23, 0);
+    assert!(schedule.active());
+    clock.set(Weekday::Sunday, 11, 0);
+    assert!(schedule.active());
+    clock.set(Weekday::Sunday, 12, 30);
+    assert!(!schedule.active());
+
+    // A broken clock doesn't keep the timer from running
+    clock.0.set(None);
+    assert!(schedule.active());
+
+    assert!(Schedule::new().is_empty());
+    assert!(Schedule::new().active());
+}
+
+#[test]
+fn scheduled_timers() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let triggered = Cell::new(0);
+    let clock = FakeClock::default();
+    clock.set(Weekday::Tuesday, 10, 0);
+
+    let work = || "mon-fri 09:00-18:00".parse().unwrap();
+    let mut timer = Xidlehook::new(vec![
+        Scheduled::new(
+            CallbackTimer::new(TEST_UNIT * 200, || triggered.set(triggered.get() | 1)),
+            Schedule::new().with_clock(clock.clone()),
+        ),
+        // No suspending during work hours...
+        Scheduled::new(
+            CallbackTimer::new(TEST_UNIT * 100, || triggered.set(triggered.get() | 1 << 1)),
+            Schedule::new()
+                .with_clock(clock.clone())
+                .with_not_during(work()),
+        ),
+        // ...but quickly at any other time
+        Scheduled::new(
+            CallbackTimer::new(TEST_UNIT * 20, || triggered.set(triggered.get() | 1 << 2)),
+            Schedule::new()
+                .with_clock(clock.clone())
+                .with_during(work()),
+        ),
+    ]);
+
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Sleep(TEST_UNIT * 200)
+    );
+    assert_eq!(timer.poll(TEST_UNIT * 200).unwrap(), Sleep(TEST_UNIT * 20));
+    assert_eq!(triggered.get(), 0b001);
+    assert_eq!(timer.poll(TEST_UNIT * 220).unwrap(), Sleep(TEST_UNIT * 200));
+    assert_eq!(triggered.get(), 0b101);
+
+    // At night, the other timer runs instead
+    triggered.set(0);
+    clock.set(Weekday::Tuesday, 23, 0);
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Sleep(TEST_UNIT * 200)
+    );
+    assert_eq!(timer.poll(TEST_UNIT * 200).unwrap(), Sleep(TEST_UNIT * 100));
+    // The disabled timer may be enabled again in the meantime
+    assert_eq!(timer.poll(TEST_UNIT * 300).unwrap(), Sleep(TEST_UNIT * 20));
+    assert_eq!(triggered.get(), 0b011);
+
+    // Work starts in a second, which changes the next timer
+    clock.0.set(Some(LocalTime {
+        second: 59,
+        ..at(Weekday::Wednesday, 8, 59)
+    }));
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Sleep(Duration::from_secs(1))
+    );
+}
+
+#[test]
+fn next_changes() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let work = Schedule::new().with_during("mon-fri 09:00-18:00".parse().unwrap());
+    assert_eq!(
+        work.next_change_at(at(Weekday::Monday, 8, 0)),
+        Some(hours(1))
+    );
+    assert_eq!(
+        work.next_change_at(at(Weekday::Monday, 9, 0)),
+        Some(hours(9))
+    );
+    assert_eq!(
+        work.next_change_at(at(Weekday::Friday, 18, 0)),
+        Some(hours(2 * 24 + 15))
+    );
+    assert_eq!(
+        work.next_change_at(LocalTime {
+            second: 30,
+            ..at(Weekday::Monday, 17, 59)
+        }),
+        Some(Duration::from_secs(30))
+    );
+
+    // Past midnight, with a break on Sundays
+    let night = Schedule::new()
+        .with_during("22:00-07:00".parse().unwrap())
+        .with_not_during("sun".parse().unwrap());
+    assert_eq!(
+        night.next_change_at(at(Weekday::Tuesday, 23, 0)),
+        Some(hours(8))
+    );
+    assert_eq!(
+        night.next_change_at(at(Weekday::Saturday, 23, 0)),
+        Some(hours(1))
+    );
+    assert_eq!(
+        night.next_change_at(at(Weekday::Sunday, 12, 0)),
+        Some(hours(12))
+    );
+
+    // Some schedules never change
+    let always = Schedule::new().with_during("mon-sun".parse().unwrap());
+    assert_eq!(always.next_change_at(at(Weekday::Monday, 8, 0)), None);
+    assert_eq!(Schedule::new().next_change(), None);
+}
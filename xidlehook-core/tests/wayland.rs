@@ -0,0 +1,227 @@
+#![cfg(feature = "wayland")]
+
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    thread,
+    time::{Duration, Instant},
+};
+use xidlehook_core::{modules::Wayland, IdleSource};
+
+const TEST_UNIT: Duration = Duration::from_millis(50);
+
+/// A stand-in compositor that speaks just enough of the Wayland wire
+/// protocol to hand out one idle notification
+struct Compositor {
+    socket: UnixStream,
+    /// The globals to advertise, as interface and version
+    globals: Vec<(&'static str, u32)>,
+    /// The interface of every object the client created
+    objects: HashMap<u32, String>,
+    /// The object id and timeout of the idle notification, once
+    /// requested
+    notification: Option<(u32, u32)>,
+}
+impl Compositor {
+    fn new(socket: UnixStream, globals: Vec<(&'static str, u32)>) -> Self {
+        let mut objects = HashMap::new();
+        objects.insert(1, String::from("wl_display"));
+        Self {
+            socket,
+            globals,
+            objects,
+            notification: None,
+        }
+    }
+
+    /// Answer requests until the client has asked for an idle
+    /// notification and synced after that, or until it hangs up
+    fn serve(mut self) -> Self {
+        while let Some((object, opcode, body)) = self.receive() {
+            let mut args = Args(&body);
+            let interface = self.objects.get(&object).cloned().unwrap_or_default();
+            match (interface.as_str(), opcode) {
+                ("wl_display", 0) => {
+                    let callback = args.uint();
+                    self.send(callback, 0, &[0]);
+                    self.send(1, 1, &[callback]);
+                    if self.notification.is_some() {
+                        break;
+                    }
+                },
+                ("wl_display", 1) => {
+                    let registry = args.uint();
+                    self.objects.insert(registry, String::from("wl_registry"));
+                    for (name, &(interface, version)) in self.globals.clone().iter().enumerate() {
+                        let name = (name + 1).try_into().unwrap();
+                        self.send_global(registry, name, interface, version);
+                    }
+                },
+                ("wl_registry", 0) => {
+                    let _name = args.uint();
+                    let interface = args.string();
+                    let _version = args.uint();
+                    self.objects.insert(args.uint(), interface);
+                },
+                ("ext_idle_notifier_v1", 1) => {
+                    let id = args.uint();
+                    let timeout = args.uint();
+                    self.notification = Some((id, timeout));
+                },
+                ("org_kde_kwin_idle", 0) => {
+                    let id = args.uint();
+                    let _seat = args.uint();
+                    self.notification = Some((id, args.uint()));
+                },
+                _ => (),
+            }
+        }
+        self
+    }
+
+    /// Send the idle (0) or resumed (1) event of the notification
+    fn notify(&mut self, opcode: u16) {
+        let (id, _) = self.notification.expect("no idle notification requested");
+        self.send(id, opcode, &[]);
+    }
+
+    fn receive(&mut self) -> Option<(u32, u16, Vec<u8>)> {
+        let mut header = [0; 8];
+        self.socket.read_exact(&mut header).ok()?;
+        let object = u32::from_ne_bytes(header[..4].try_into().unwrap());
+        let word = u32::from_ne_bytes(header[4..].try_into().unwrap());
+        let mut body = vec![0; (word >> 16) as usize - header.len()];
+        self.socket.read_exact(&mut body).ok()?;
+        Some((object, (word & 0xffff) as u16, body))
+    }
+
+    fn send(&mut self, object: u32, opcode: u16, args: &[u32]) {
+        let body: Vec<u8> = args.iter().flat_map(|arg| arg.to_ne_bytes()).collect();
+        self.send_raw(object, opcode, &body);
+    }
+
+    fn send_global(&mut self, registry: u32, name: u32, interface: &str, version: u32) {
+        let mut body = name.to_ne_bytes().to_vec();
+        let len: u32 = (interface.len() + 1).try_into().unwrap();
+        body.extend_from_slice(&len.to_ne_bytes());
+        body.extend_from_slice(interface.as_bytes());
+        // The terminating nul byte, and padding to 32 bits
+        body.resize((body.len() + 4) & !3, 0);
+        body.extend_from_slice(&version.to_ne_bytes());
+        self.send_raw(registry, 0, &body);
+    }
+
+    fn send_raw(&mut self, object: u32, opcode: u16, body: &[u8]) {
+        let size: u32 = (body.len() + 8).try_into().unwrap();
+        let mut message = object.to_ne_bytes().to_vec();
+        message.extend_from_slice(&(size << 16 | u32::from(opcode)).to_ne_bytes());
+        message.extend_from_slice(body);
+        self.socket.write_all(&message).unwrap();
+    }
+}
+
+/// Reads the arguments of a request
+struct Args<'a>(&'a [u8]);
+impl Args<'_> {
+    fn uint(&mut self) -> u32 {
+        let (arg, rest) = self.0.split_at(4);
+        self.0 = rest;
+        u32::from_ne_bytes(arg.try_into().unwrap())
+    }
+    fn string(&mut self) -> String {
+        let len = self.uint() as usize;
+        let padded = (len + 3) & !3;
+        let (arg, rest) = self.0.split_at(padded);
+        self.0 = rest;
+        String::from_utf8(arg[..len - 1].to_vec()).unwrap()
+    }
+}
+
+/// Connect to a stand-in compositor with these globals
+fn connect(globals: Vec<(&'static str, u32)>) -> (xidlehook_core::Result<Wayland>, Compositor) {
+    let (client, server) = UnixStream::pair().unwrap();
+    let compositor = thread::spawn(move || Compositor::new(server, globals).serve());
+    let wayland = Wayland::from_socket(client, TEST_UNIT * 10);
+    (wayland, compositor.join().unwrap())
+}
+
+/// Wait for the event thread to pick up an event
+fn wait_for_idle<F: Fn(Duration) -> bool>(wayland: &Wayland, done: F) -> Duration {
+    let start = Instant::now();
+    loop {
+        let idle = wayland.get_idle().unwrap();
+        if done(idle) {
+            return idle;
+        }
+        assert!(start.elapsed() < TEST_UNIT * 100, "no event arrived");
+        thread::sleep(TEST_UNIT / 10);
+    }
+}
+
+#[test]
+fn ext_idle_notify() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let (wayland, mut compositor) = connect(vec![
+        ("wl_seat", 1),
+        ("org_kde_kwin_idle", 1),
+        ("ext_idle_notifier_v1", 1),
+    ]);
+    let wayland = wayland.unwrap();
+
+    // The newer protocol is preferred
+    assert_eq!(wayland.protocol(), "ext-idle-notify-v1");
+    assert_eq!(
+        compositor.notification.map(|(_, timeout)| timeout),
+        Some(500)
+    );
+
+    // Not idle until the compositor says so, and then already idle
+    // for the whole timeout
+    assert_eq!(wayland.get_idle().unwrap(), Duration::default());
+    compositor.notify(0);
+    let idle = wait_for_idle(&wayland, |idle| idle > Duration::default());
+    assert!(idle >= TEST_UNIT * 10);
+
+    compositor.notify(1);
+    wait_for_idle(&wayland, |idle| idle == Duration::default());
+
+    // Losing the compositor is an error
+    drop(compositor);
+    let start = Instant::now();
+    while wayland.get_idle().is_ok() {
+        assert!(start.elapsed() < TEST_UNIT * 100);
+        thread::sleep(TEST_UNIT / 10);
+    }
+}
+
+#[test]
+fn kde_idle() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let (wayland, mut compositor) = connect(vec![("wl_seat", 1), ("org_kde_kwin_idle", 1)]);
+    let wayland = wayland.unwrap();
+
+    assert_eq!(wayland.protocol(), "org_kde_kwin_idle");
+    assert_eq!(
+        compositor.notification.map(|(_, timeout)| timeout),
+        Some(500)
+    );
+
+    compositor.notify(0);
+    let idle = wait_for_idle(&wayland, |idle| idle > Duration::default());
+    assert!(idle >= TEST_UNIT * 10);
+    compositor.notify(1);
+    wait_for_idle(&wayland, |idle| idle == Duration::default());
+}
+
+#[test]
+fn unsupported_compositor() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let (wayland, compositor) = connect(vec![("wl_seat", 1)]);
+    assert!(wayland.is_err());
+    assert!(compositor.notification.is_none());
+}
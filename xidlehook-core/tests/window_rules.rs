@@ -0,0 +1,33 @@
+use xidlehook_core::modules::xcb::{WindowProperty, WindowRule};
+
+#[test]
+fn parse() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let rule: WindowRule = "class=Zoom".parse().unwrap();
+    assert_eq!(rule, WindowRule::new(WindowProperty::Class, "zoom"));
+    assert_eq!(rule.to_string(), "class=zoom");
+
+    // Only the first = separates the property from the value
+    let rule: WindowRule = "title=a=b".parse().unwrap();
+    assert_eq!(rule, WindowRule::new(WindowProperty::Title, "a=b"));
+
+    assert_eq!(
+        "role=browser".parse::<WindowRule>().unwrap().property(),
+        WindowProperty::Role
+    );
+
+    assert!("zoom".parse::<WindowRule>().is_err());
+    assert!("name=zoom".parse::<WindowRule>().is_err());
+    assert!("class=".parse::<WindowRule>().is_err());
+}
+
+#[test]
+fn matches() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let rule = WindowRule::new(WindowProperty::Title, "Zoom Meeting");
+    assert!(rule.matches("Zoom Meeting"));
+    assert!(rule.matches("zoom meeting - 40 minutes left"));
+    assert!(!rule.matches("Zoom"));
+}
@@ -1,5 +1,5 @@
 use std::{
-    io::{prelude::*, BufReader, LineWriter},
+    io::{self, prelude::*, BufReader, LineWriter},
     os::unix::net::UnixStream,
     time::Duration,
 };
@@ -36,10 +36,15 @@ struct Opt {
 enum Subcommands {
     /// Create a new timer
     Add {
-        /// The desired idle time, in seconds, which the timer will go
-        /// off after
+        /// The chain to add the timer to, as named in the config file.
+        /// Defaults to the main chain.
         #[structopt(long)]
-        time: u64,
+        chain: Option<String>,
+        /// The desired idle time which the timer will go off after,
+        /// such as "90s", "5m" or "1h30m". A bare number is a number
+        /// of seconds.
+        #[structopt(long, parse(try_from_str = xidlehook_core::duration::parse))]
+        time: Duration,
         /// Where to insert this timer. To insert it at the beginning,
         /// set this to 0. To insert it at the end, skip this.
         #[structopt(long)]
@@ -59,9 +64,16 @@ enum Subcommands {
         /// deactivation = next timer's activation basically)
         #[structopt(long, value_terminator = ";", allow_hyphen_values = true)]
         deactivation: Vec<String>,
+        /// Remove the timer again after this long, such as "8h"
+        #[structopt(long, parse(try_from_str = xidlehook_core::duration::parse))]
+        timeout: Option<Duration>,
     },
     /// A control operation
     Control {
+        /// The chain of the timers, as named in the config file.
+        /// Defaults to the main chain.
+        #[structopt(long)]
+        chain: Option<String>,
         /// The timers which this operation should apply to. Leave
         /// empty for all timers.
         #[structopt(long)]
@@ -69,22 +81,56 @@ enum Subcommands {
         /// Which action to cause on the selected timers
         #[structopt(long, possible_values = &OptAction::variants(), case_insensitive = true)]
         action: OptAction,
+        /// Undo the action after this long, such as "8h". Can't be used
+        /// with trigger.
+        #[structopt(long, parse(try_from_str = xidlehook_core::duration::parse))]
+        timeout: Option<Duration>,
     },
     /// Pretend the user moved the mouse or otherwise became active, reset the idle timer and start
     /// triggering timers.
     ResetIdle,
     /// Disable xidlehook until the user becomes active again. Basically act is if there were no
-    /// more timers left to activate. The xidlehook-client commands that modify timers already use
+    /// more timers left to activate in any chain. The xidlehook-client commands that modify timers already use
     /// this behaviour implicitly to avoid potential glitches that could arise when you modify
     /// currently running timers.
     StopTimers,
     /// Query the list of timers
     Query {
+        /// The chain of the timers, as named in the config file.
+        /// Defaults to the main chain.
+        #[structopt(long)]
+        chain: Option<String>,
         /// The timers which this operation should apply to. Leave
         /// empty for all timers.
         #[structopt(long)]
         timer: Vec<socket::TimerId>,
     },
+    /// Show the current state of the chains, such as how long until the
+    /// next timer and what's stopping it
+    Status,
+    /// Keep timers from activating until uninhibited, even if the user
+    /// becomes active in between. Prints a cookie to uninhibit with.
+    Inhibit {
+        /// Why you're inhibiting, such as "Watching a movie"
+        #[structopt(long)]
+        reason: String,
+        /// Who is inhibiting
+        #[structopt(long, default_value = "xidlehook-client")]
+        owner: String,
+        /// Uninhibit automatically after this long, such as "2h"
+        #[structopt(long, parse(try_from_str = xidlehook_core::duration::parse))]
+        timeout: Option<Duration>,
+    },
+    /// Release an inhibitor
+    Uninhibit {
+        /// The cookie that was printed by the inhibit command
+        #[structopt(long)]
+        cookie: socket::Cookie,
+    },
+    /// Print a line of JSON whenever a timer is activated, aborted or
+    /// deactivated, or the user becomes idle or active. Useful for
+    /// status bars.
+    Subscribe,
 }
 
 fn filter(filter: Vec<socket::TimerId>) -> socket::Filter {
@@ -99,19 +145,29 @@ fn main() -> xidlehook_core::Result<()> {
     let opt = Opt::from_args();
     let packet = match opt.cmd {
         Subcommands::Add {
+            chain,
             time,
             index,
             activation,
             abortion,
             deactivation,
+            timeout,
         } => socket::Message::Add(socket::Add {
-            time: Duration::from_secs(time),
+            chain,
+            time,
             index,
             activation,
             abortion,
             deactivation,
+            timeout,
         }),
-        Subcommands::Control { timer, action } => socket::Message::Control(socket::Control {
+        Subcommands::Control {
+            chain,
+            timer,
+            action,
+            timeout,
+        } => socket::Message::Control(socket::Control {
+            chain,
             timer: filter(timer),
             action: match action {
                 OptAction::Enable => socket::Action::Enable,
@@ -119,12 +175,28 @@ fn main() -> xidlehook_core::Result<()> {
                 OptAction::Trigger => socket::Action::Trigger,
                 OptAction::Delete => socket::Action::Delete,
             },
+            timeout,
         }),
         Subcommands::ResetIdle => socket::Message::ResetIdle,
         Subcommands::StopTimers => socket::Message::StopTimers,
-        Subcommands::Query { timer } => socket::Message::Query(socket::Query {
+        Subcommands::Query { chain, timer } => socket::Message::Query(socket::Query {
+            chain,
             timer: filter(timer),
         }),
+        Subcommands::Status => socket::Message::Status,
+        Subcommands::Inhibit {
+            reason,
+            owner,
+            timeout,
+        } => socket::Message::Inhibit(socket::Inhibit {
+            reason,
+            owner,
+            timeout,
+        }),
+        Subcommands::Uninhibit { cookie } => {
+            socket::Message::Uninhibit(socket::Uninhibit { cookie })
+        },
+        Subcommands::Subscribe => socket::Message::Subscribe,
     };
 
     let stream = UnixStream::connect(opt.socket)?;
@@ -135,6 +207,16 @@ fn main() -> xidlehook_core::Result<()> {
     writer.write_all(&[b'\n'])?;
     writer.flush()?;
 
+    if let socket::Message::Subscribe = packet {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        for line in reader.lines() {
+            writeln!(stdout, "{}", line?)?;
+            stdout.flush()?;
+        }
+        return Ok(());
+    }
+
     if let Some(line) = reader.lines().next() {
         let reply: socket::Reply = serde_json::from_str(&line?)?;
         println!("{:#?}", reply);
@@ -0,0 +1,719 @@
+//! The configuration file. Everything that can be passed on the
+//! command line can also be written down here, along with timer
+//! options that would be unwieldy as arguments.
+//!
+//! ```toml
+//! not_when_fullscreen = true
+//! fullscreen_mode = "visible"
+//! fullscreen_outputs = ["primary"]
+//! not_when_window = ["class=zoom"]
+//!
+//! [[timer]]
+//! time = "1m"
+//! activation = "xrandr --output eDP-1 --brightness .1"
+//! abortion = "xrandr --output eDP-1 --brightness 1"
+//!
+//! [[timer]]
+//! time = "10s"
+//! activation = ["i3lock", "--nofork"]
+//!
+//! [[timer]]
+//! time = "1h"
+//! activation = ["systemctl", "suspend"]
+//! not_during = ["mon-fri 09:00-18:00"]
+//!
+//! [logind]
+//! lock_timer = 1
+//!
+//! [[power.battery]]
+//! time = "30s"
+//! activation = "xrandr --output eDP-1 --brightness .1"
+//! abortion = "xrandr --output eDP-1 --brightness 1"
+//!
+//! [[power.battery]]
+//! time = "10s"
+//! activation = ["i3lock", "--nofork"]
+//!
+//! [corners]
+//! lock = ["top-left"]
+//! never = ["bottom-right"]
+//! timer = 1
+//! delay = "1s"
+//!
+//! [[chain]]
+//! name = "music"
+//!
+//! [[chain.timer]]
+//! time = "10m"
+//! activation = ["playerctl", "pause"]
+//! ```
+
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use serde::{de, Deserialize, Deserializer};
+#[cfg(target_os = "linux")]
+use xidlehook_core::modules::process::ProcessPattern;
+use xidlehook_core::modules::{
+    xcb::{Corner, FullscreenMode, OutputSelector, WindowRule},
+    AudioFilter,
+};
+use xidlehook_core::schedule::{Period, Schedule};
+
+use crate::{
+    power::Profile,
+    socket::models::{human_duration, TimerId},
+    timers::CmdTimer,
+    AudioBackend, Opt, MAIN_CHAIN,
+};
+
+/// See the module-level documentation
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub once: bool,
+    pub not_when_fullscreen: bool,
+    #[serde(deserialize_with = "from_str")]
+    pub fullscreen_mode: FullscreenMode,
+    #[serde(deserialize_with = "from_strs")]
+    pub fullscreen_outputs: Vec<OutputSelector>,
+    #[serde(deserialize_with = "from_strs")]
+    pub not_when_window: Vec<WindowRule>,
+    #[serde(deserialize_with = "from_strs")]
+    pub not_when_focused_window: Vec<WindowRule>,
+    pub not_when_audio: bool,
+    pub audio_backend: Option<AudioBackend>,
+    pub audio_ignore: Vec<String>,
+    pub audio_ignore_muted: bool,
+    pub audio_sinks: Vec<String>,
+    pub audio_capture: bool,
+    pub not_when_microphone: bool,
+    pub not_when_camera: bool,
+    #[cfg(target_os = "linux")]
+    #[serde(deserialize_with = "from_strs")]
+    pub not_when_process: Vec<ProcessPattern>,
+    #[cfg(target_os = "linux")]
+    pub process_own_only: bool,
+    pub detect_sleep: bool,
+    pub socket: Option<String>,
+    pub persist: bool,
+    pub state_file: Option<PathBuf>,
+    pub freedesktop: bool,
+    pub logind: Option<LogindConfig>,
+    pub corners: CornersConfig,
+    pub power: PowerConfig,
+
+    #[serde(rename = "timer")]
+    pub timers: Vec<TimerConfig>,
+    #[serde(rename = "chain")]
+    pub chains: Vec<ChainConfig>,
+}
+impl Config {
+    /// Read and parse a config file
+    pub fn from_file(path: &Path) -> xidlehook_core::Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        toml::from_str(&text)
+            .map_err(|err| format!("failed to parse {}: {}", path.display(), err).into())
+    }
+
+    /// Returns `$XDG_CONFIG_HOME/xidlehook/config.toml`, falling back
+    /// to `~/.config` as the config directory
+    pub fn default_path() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .map(|dir| dir.join("xidlehook").join("config.toml"))
+    }
+
+    /// Returns `$XDG_STATE_HOME/xidlehook/state.json`, falling back to
+    /// `~/.local/state` as the state directory
+    pub fn default_state_path() -> Option<PathBuf> {
+        env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| {
+                env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("state"))
+            })
+            .map(|dir| dir.join("xidlehook").join("state.json"))
+    }
+
+    /// Returns where to save the changes made over the socket, if
+    /// anywhere. Giving a state file implies `persist`.
+    pub fn state_path(&self) -> Option<PathBuf> {
+        self.state_file.clone().or_else(|| {
+            if self.persist {
+                Self::default_state_path()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the path of the config file that `load` reads, if any
+    pub fn path(opt: &Opt) -> Option<PathBuf> {
+        opt.config
+            .clone()
+            .or_else(|| Self::default_path().filter(|path| path.exists()))
+    }
+
+    /// Load the config file (if any) and apply the command line
+    /// options on top of it. Flags are combined, while timers passed
+    /// on the command line replace the ones in the file.
+    pub fn load(opt: &Opt) -> xidlehook_core::Result<Self> {
+        let mut config = match Self::path(opt) {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+
+        config.once |= opt.once;
+        config.not_when_fullscreen |= opt.not_when_fullscreen;
+        if let Some(mode) = opt.fullscreen_mode {
+            config.fullscreen_mode = mode;
+        }
+        config
+            .fullscreen_outputs
+            .extend(opt.fullscreen_output.iter().cloned());
+        config
+            .not_when_window
+            .extend(opt.not_when_window.iter().cloned());
+        config
+            .not_when_focused_window
+            .extend(opt.not_when_focused_window.iter().cloned());
+        config.detect_sleep |= opt.detect_sleep;
+        #[cfg(any(feature = "pulse", feature = "pipewire"))]
+        {
+            config.not_when_audio |= opt.not_when_audio;
+            if opt.audio_backend.is_some() {
+                config.audio_backend = opt.audio_backend;
+            }
+            config.audio_ignore.extend(opt.audio_ignore.iter().cloned());
+            config.audio_ignore_muted |= opt.audio_ignore_muted;
+            config.audio_sinks.extend(opt.audio_sink.iter().cloned());
+            config.audio_capture |= opt.audio_capture;
+            config.not_when_microphone |= opt.not_when_microphone;
+        }
+        #[cfg(target_os = "linux")]
+        {
+            config.not_when_camera |= opt.not_when_camera;
+            config
+                .not_when_process
+                .extend(opt.not_when_process.iter().cloned());
+            config.process_own_only |= opt.process_own_only;
+        }
+        #[cfg(feature = "freedesktop")]
+        {
+            config.freedesktop |= opt.freedesktop;
+        }
+        #[cfg(feature = "logind")]
+        {
+            if opt.logind && config.logind.is_none() {
+                config.logind = Some(LogindConfig::default());
+            }
+        }
+        config.corners.lock.extend(opt.lock_corner.iter().copied());
+        config
+            .corners
+            .never
+            .extend(opt.never_corner.iter().copied());
+        if let Some(timer) = opt.corner_timer {
+            config.corners.timer = timer;
+        }
+        if let Some(delay) = opt.corner_delay {
+            config.corners.delay = delay;
+        }
+        if let Some(size) = opt.corner_size {
+            config.corners.size = size;
+        }
+        if opt.socket.is_some() {
+            config.socket = opt.socket.clone();
+        }
+        config.persist |= opt.persist;
+        if opt.state_file.is_some() {
+            config.state_file = opt.state_file.clone();
+        }
+
+        if !opt.timer.is_empty() {
+            config.timers.clear();
+
+            // clap-rs will ensure there are always a multiple of 3 arguments
+            for chunk in opt.timer.chunks(3) {
+                let time = xidlehook_core::duration::parse(&chunk[0])?;
+                config.timers.push(TimerConfig {
+                    time,
+                    activation: Cmd::Shell(chunk[1].clone()),
+                    abortion: Cmd::Shell(chunk[2].clone()),
+                    deactivation: Cmd::default(),
+                    disabled: false,
+                    during: Vec::new(),
+                    not_during: Vec::new(),
+                });
+            }
+        }
+
+        // Timers are referred to by index, so they have to exist in
+        // every set that may be used
+        let sets: Vec<&[TimerConfig]> = if config.power.enabled() {
+            Profile::ALL
+                .iter()
+                .map(|&profile| config.timers(Some(profile)))
+                .collect()
+        } else {
+            vec![&config.timers]
+        };
+        if sets.iter().any(|timers| timers.is_empty()) {
+            return Err("no timers specified, use --timer or a config file".into());
+        }
+        let timers = sets.iter().map(|timers| timers.len()).min().unwrap_or(0);
+        if !config.corners.lock.is_empty() && usize::from(config.corners.timer) >= timers {
+            return Err(format!(
+                "the lock corners refer to timer {}, but there are only {}",
+                config.corners.timer, timers
+            )
+            .into());
+        }
+        for (i, chain) in config.chains.iter().enumerate() {
+            if chain.name == MAIN_CHAIN {
+                return Err(format!("the chain name {:?} is reserved", MAIN_CHAIN).into());
+            }
+            if config.chains[..i]
+                .iter()
+                .any(|other| other.name == chain.name)
+            {
+                return Err(format!("there are several chains named {:?}", chain.name).into());
+            }
+            if chain.timers.is_empty() {
+                return Err(format!("the chain {:?} has no timers", chain.name).into());
+            }
+        }
+        if let Some(ref logind) = config.logind {
+            let ids = logind
+                .lock_timer
+                .iter()
+                .chain(&logind.idle_hint_timer)
+                .chain(&logind.sleep_timer);
+            for &id in ids {
+                if usize::from(id) >= timers {
+                    return Err(format!(
+                        "logind refers to timer {}, but there are only {}",
+                        id, timers
+                    )
+                    .into());
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the timers to use with this power profile, falling
+    /// back to the `[[timer]]`s
+    pub fn timers(&self, profile: Option<Profile>) -> &[TimerConfig] {
+        let preferred = match profile {
+            Some(Profile::Ac) => Some(&self.power.ac),
+            Some(Profile::Battery) => Some(&self.power.battery),
+            Some(Profile::LowBattery) => Some(&self.power.low_battery),
+            None => None,
+        };
+        let fallback = match profile {
+            Some(Profile::LowBattery) => Some(&self.power.battery),
+            _ => None,
+        };
+        preferred
+            .into_iter()
+            .chain(fallback)
+            .find(|timers| !timers.is_empty())
+            .unwrap_or(&self.timers)
+    }
+
+    /// Which streams `not_when_audio` counts
+    #[cfg_attr(not(any(feature = "pulse", feature = "pipewire")), allow(dead_code))]
+    pub fn audio_filter(&self) -> AudioFilter {
+        let mut filter = AudioFilter::default()
+            .with_ignore_muted(self.audio_ignore_muted)
+            .with_capture(self.audio_capture);
+        for application in &self.audio_ignore {
+            filter = filter.with_ignored_application(application);
+        }
+        for sink in &self.audio_sinks {
+            filter = filter.with_sink(sink);
+        }
+        filter
+    }
+}
+
+/// Deserialize a string, such as a fullscreen mode, with `FromStr`
+fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(de::Error::custom)
+}
+
+/// Deserialize a list of strings, such as window rules, with `FromStr`
+fn from_strs<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|s| s.parse().map_err(de::Error::custom))
+        .collect()
+}
+
+/// The `[logind]` table. Having one at all makes xidlehook reset the
+/// chain when resuming from suspend.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogindConfig {
+    /// Trigger this timer when the session is locked, such as with
+    /// `loginctl lock-session`
+    pub lock_timer: Option<TimerId>,
+    /// Set the session's idle hint once this timer activates
+    pub idle_hint_timer: Option<TimerId>,
+    /// Trigger this timer before suspending, and delay the suspend
+    /// until its activation command exits or closes the file
+    /// descriptor in `XIDLEHOOK_SLEEP_LOCK_FD`
+    pub sleep_timer: Option<TimerId>,
+}
+
+/// The `[corners]` table. Resting the pointer in a lock corner
+/// triggers a timer, while resting it in a never corner keeps the
+/// timers from running until it leaves.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CornersConfig {
+    #[serde(deserialize_with = "from_strs")]
+    pub lock: Vec<Corner>,
+    #[serde(deserialize_with = "from_strs")]
+    pub never: Vec<Corner>,
+    /// The timer that lock corners trigger
+    pub timer: TimerId,
+    /// How long the pointer has to rest in a corner
+    #[serde(with = "human_duration")]
+    pub delay: Duration,
+    /// The size of a corner, in pixels
+    pub size: u16,
+}
+impl Default for CornersConfig {
+    fn default() -> Self {
+        // Same as xautolock
+        Self {
+            lock: Vec::new(),
+            never: Vec::new(),
+            timer: 0,
+            delay: Duration::from_secs(5),
+            size: 10,
+        }
+    }
+}
+impl CornersConfig {
+    /// Returns true if any corner does something
+    pub fn enabled(&self) -> bool {
+        !self.lock.is_empty() || !self.never.is_empty()
+    }
+}
+
+/// The `[power]` table, with timers to use instead of the
+/// `[[timer]]`s depending on the power source. The low battery timers
+/// fall back to the battery ones, and any empty set falls back to the
+/// `[[timer]]`s.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerConfig {
+    pub ac: Vec<TimerConfig>,
+    pub battery: Vec<TimerConfig>,
+    pub low_battery: Vec<TimerConfig>,
+    /// The battery charge in percent at which the low battery timers
+    /// are used
+    pub low_capacity: u8,
+}
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            ac: Vec::new(),
+            battery: Vec::new(),
+            low_battery: Vec::new(),
+            low_capacity: 10,
+        }
+    }
+}
+impl PowerConfig {
+    /// Returns true if any timers depend on the power source
+    pub fn enabled(&self) -> bool {
+        !self.ac.is_empty() || !self.battery.is_empty() || !self.low_battery.is_empty()
+    }
+}
+
+/// One `[[chain]]` table, with timers that run next to the
+/// `[[timer]]`s. Each chain counts the same idle time, but has its own
+/// position and abort state, so one chain can be stopped from running
+/// while the others carry on.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainConfig {
+    /// What the socket calls this chain
+    pub name: String,
+    #[serde(rename = "timer")]
+    pub timers: Vec<TimerConfig>,
+}
+
+/// One `[[timer]]` table
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimerConfig {
+    /// The amount of inactivity which should trigger this timer
+    #[serde(with = "human_duration")]
+    pub time: Duration,
+    #[serde(default)]
+    pub activation: Cmd,
+    #[serde(default)]
+    pub abortion: Cmd,
+    #[serde(default)]
+    pub deactivation: Cmd,
+    #[serde(default)]
+    pub disabled: bool,
+    /// Only enable the timer during these periods, such as
+    /// "mon-fri 09:00-18:00"
+    #[serde(default, deserialize_with = "from_strs")]
+    pub during: Vec<Period>,
+    /// Never enable the timer during these periods
+    #[serde(default, deserialize_with = "from_strs")]
+    pub not_during: Vec<Period>,
+}
+impl TimerConfig {
+    pub fn to_timer(&self) -> CmdTimer {
+        let mut timer = CmdTimer::from_parts(
+            self.time,
+            self.activation.to_argv(),
+            self.abortion.to_argv(),
+            self.deactivation.to_argv(),
+        );
+        timer.set_disabled(self.disabled);
+        let mut schedule = Schedule::new();
+        for &period in &self.during {
+            schedule = schedule.with_during(period);
+        }
+        for &period in &self.not_during {
+            schedule = schedule.with_not_during(period);
+        }
+        timer.set_schedule(schedule);
+        timer
+    }
+}
+
+/// A command is either a string which is passed through `/bin/sh -c`,
+/// or a list of arguments which is executed directly
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Cmd {
+    Shell(String),
+    Argv(Vec<String>),
+}
+impl Default for Cmd {
+    fn default() -> Self {
+        Cmd::Argv(Vec::new())
+    }
+}
+impl Cmd {
+    pub fn to_argv(&self) -> Vec<String> {
+        match self {
+            Cmd::Shell(cmd) if cmd.is_empty() => Vec::new(),
+            Cmd::Shell(cmd) => vec!["/bin/sh".into(), "-c".into(), cmd.clone()],
+            Cmd::Argv(argv) => argv.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use structopt::StructOpt;
+
+    use crate::tests::TempConfig;
+
+    /// The example from the module-level documentation
+    const EXAMPLE: &str = r#"
+        not_when_fullscreen = true
+        fullscreen_mode = "visible"
+        fullscreen_outputs = ["primary"]
+        not_when_window = ["class=zoom"]
+
+        [[timer]]
+        time = "1m"
+        activation = "xrandr --output eDP-1 --brightness .1"
+        abortion = "xrandr --output eDP-1 --brightness 1"
+
+        [[timer]]
+        time = "10s"
+        activation = ["i3lock", "--nofork"]
+
+        [[timer]]
+        time = "1h"
+        activation = ["systemctl", "suspend"]
+        not_during = ["mon-fri 09:00-18:00"]
+
+        [logind]
+        lock_timer = 1
+
+        [[power.battery]]
+        time = "30s"
+        activation = "xrandr --output eDP-1 --brightness .1"
+        abortion = "xrandr --output eDP-1 --brightness 1"
+
+        [[power.battery]]
+        time = "10s"
+        activation = ["i3lock", "--nofork"]
+
+        [corners]
+        lock = ["top-left"]
+        never = ["bottom-right"]
+        timer = 1
+        delay = "1s"
+
+        [[chain]]
+        name = "music"
+
+        [[chain.timer]]
+        time = "10m"
+        activation = ["playerctl", "pause"]
+    "#;
+
+    /// Load a config file with these command line arguments
+    fn load(name: &str, text: &str, args: &[&str]) -> xidlehook_core::Result<Config> {
+        let file = TempConfig::new(name, text);
+        let path = file.path().to_str().unwrap();
+        let opt = Opt::from_iter(["xidlehook", "--config", path].iter().chain(args));
+        Config::load(&opt)
+    }
+
+    #[test]
+    fn example() {
+        let config: Config = toml::from_str(EXAMPLE).unwrap();
+        assert!(config.not_when_fullscreen);
+        assert_eq!(config.fullscreen_mode, FullscreenMode::Visible);
+        assert_eq!(config.fullscreen_outputs, vec![OutputSelector::Primary]);
+        assert_eq!(config.not_when_window.len(), 1);
+
+        assert_eq!(config.timers.len(), 3);
+        assert_eq!(config.timers[0].time, Duration::from_secs(60));
+        assert_eq!(
+            config.timers[0].activation.to_argv(),
+            vec!["/bin/sh", "-c", "xrandr --output eDP-1 --brightness .1"]
+        );
+        assert_eq!(
+            config.timers[1].activation.to_argv(),
+            vec!["i3lock", "--nofork"]
+        );
+        assert!(config.timers[1].deactivation.to_argv().is_empty());
+        assert_eq!(config.timers[2].time, Duration::from_secs(3600));
+        assert_eq!(config.timers[2].not_during.len(), 1);
+
+        assert_eq!(
+            config.logind,
+            Some(LogindConfig {
+                lock_timer: Some(1),
+                ..LogindConfig::default()
+            })
+        );
+        assert_eq!(config.power.battery.len(), 2);
+        assert_eq!(config.timers(Some(Profile::LowBattery)).len(), 2);
+        assert_eq!(config.timers(Some(Profile::Ac)).len(), 3);
+        assert_eq!(
+            config.corners,
+            CornersConfig {
+                lock: vec![Corner::TopLeft],
+                never: vec![Corner::BottomRight],
+                timer: 1,
+                delay: Duration::from_secs(1),
+                ..CornersConfig::default()
+            }
+        );
+        assert_eq!(config.chains.len(), 1);
+        assert_eq!(config.chains[0].name, "music");
+        assert_eq!(config.chains[0].timers.len(), 1);
+
+        // It's valid, too
+        load("example", EXAMPLE, &[]).unwrap();
+    }
+
+    #[test]
+    fn unknown_fields() {
+        assert!(toml::from_str::<Config>("not_when_fulscreen = true").is_err());
+        assert!(
+            toml::from_str::<Config>("[[timer]]\ntime = \"1m\"\nactivaton = \"true\"").is_err()
+        );
+        assert!(toml::from_str::<Config>("[corners]\nlocks = [\"top-left\"]").is_err());
+    }
+
+    #[test]
+    fn command_line_overrides_file() {
+        let config = load(
+            "overrides",
+            EXAMPLE,
+            &[
+                "--timer",
+                "5m",
+                "dim",
+                "",
+                "--timer",
+                "10s",
+                "lock",
+                "",
+                "--fullscreen-mode",
+                "focused",
+                "--not-when-window",
+                "title=meeting",
+                "--corner-delay",
+                "2s",
+            ],
+        )
+        .unwrap();
+        // Timers replace the ones in the file
+        assert_eq!(config.timers.len(), 2);
+        assert_eq!(config.timers[0].time, Duration::from_secs(300));
+        assert_eq!(
+            config.timers[1].activation.to_argv(),
+            vec!["/bin/sh", "-c", "lock"]
+        );
+        assert!(config.timers[0].abortion.to_argv().is_empty());
+        // Options replace the file's value
+        assert_eq!(config.fullscreen_mode, FullscreenMode::Focused);
+        assert_eq!(config.corners.delay, Duration::from_secs(2));
+        // Lists are combined
+        assert_eq!(config.not_when_window.len(), 2);
+        // Flags can only be turned on
+        assert!(config.not_when_fullscreen);
+    }
+
+    #[test]
+    fn invalid() {
+        let err = load("no-timers", "not_when_fullscreen = true", &[]).unwrap_err();
+        assert!(err.to_string().contains("no timers"), "{}", err);
+
+        let empty_chain = "[[timer]]\ntime = \"1m\"\n\n[[chain]]\nname = \"music\"\ntimer = []";
+        let err = load("empty-chain", empty_chain, &[]).unwrap_err();
+        assert!(err.to_string().contains("has no timers"), "{}", err);
+
+        let err = load("bad-duration", "[[timer]]\ntime = \"1 fortnight\"", &[]).unwrap_err();
+        assert!(err.to_string().contains("failed to parse"), "{}", err);
+
+        let err = load("bad-timer", EXAMPLE, &["--timer", "soon", "lock", ""]).unwrap_err();
+        assert!(!err.to_string().is_empty());
+
+        let err = load("bad-corner", EXAMPLE, &["--corner-timer", "3"]).unwrap_err();
+        assert!(err.to_string().contains("lock corners"), "{}", err);
+    }
+}
@@ -0,0 +1,219 @@
+//! Hot corners, like xautolock's `-corners`: resting the pointer in a
+//! lock corner triggers a timer of the main chain right away, such as
+//! the one that locks the screen, while resting it in a never corner
+//! takes an inhibitor until the pointer leaves again. Each monitor has
+//! its own corners. The pointer is looked up from time to time by a
+//! thread of its own, but only when any corners are configured.
+
+use crate::{config::CornersConfig, App};
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc as std_mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, info, warn};
+use tokio::sync::mpsc;
+use xidlehook_core::{
+    modules::{
+        inhibit::Cookie,
+        xcb::{Corner, Xcb},
+    },
+    Progress,
+};
+
+/// How often to look at where the pointer is
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Looks at where the pointer is from a thread of its own, with its
+/// own X connection, so that the main loop only wakes up once the
+/// pointer has rested in a corner or left it again
+#[derive(Debug)]
+pub struct CornerWatcher {
+    /// `Some(corner)` once the pointer has rested in a corner for long
+    /// enough, `None` once it left that corner again
+    events: mpsc::UnboundedReceiver<Option<Corner>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+impl CornerWatcher {
+    /// Start watching corners of `size` pixels, which the pointer has
+    /// to rest in for `delay`
+    pub fn spawn(size: u16, delay: Duration) -> xidlehook_core::Result<Self> {
+        let (tx, events) = mpsc::unbounded_channel();
+        let (ready_tx, ready_rx) = std_mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            thread::Builder::new()
+                .name(String::from("corners"))
+                .spawn(move || {
+                    // X connections can't be sent to other threads
+                    let xcb = match Xcb::new() {
+                        Ok(xcb) => xcb,
+                        Err(err) => {
+                            let _ = ready_tx.send(Err(err.to_string()));
+                            return;
+                        },
+                    };
+                    let _ = ready_tx.send(Ok(()));
+                    watch(&xcb, size, delay, &stop, &tx);
+                })?
+        };
+        let watcher = Self {
+            events,
+            stop,
+            thread: Some(thread),
+        };
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(watcher),
+            Ok(Err(err)) => Err(err.into()),
+            Err(_) => Err("the corner watcher stopped".into()),
+        }
+    }
+
+    /// Wait for the pointer to rest in a corner or leave it. Returns
+    /// `None` if the watcher stopped.
+    pub async fn recv(&mut self) -> Option<Option<Corner>> {
+        self.events.recv().await
+    }
+}
+impl Drop for CornerWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The corner watcher's thread
+fn watch(
+    xcb: &Xcb,
+    size: u16,
+    delay: Duration,
+    stop: &AtomicBool,
+    tx: &mpsc::UnboundedSender<Option<Corner>>,
+) {
+    // The corner the pointer is in and since when, and whether the
+    // main loop has been told about it
+    let mut resting: Option<(Corner, Instant)> = None;
+    let mut told = false;
+
+    while !stop.load(Ordering::Relaxed) {
+        match xcb.pointer_corner(size) {
+            Ok(corner) => {
+                let now = Instant::now();
+                match (resting, corner) {
+                    (Some((resting, _)), Some(corner)) if resting == corner => (),
+                    (_, corner) => {
+                        if told {
+                            told = false;
+                            if tx.send(None).is_err() {
+                                break;
+                            }
+                        }
+                        resting = corner.map(|corner| (corner, now));
+                    },
+                }
+                if let Some((corner, since)) = resting {
+                    if !told && now.duration_since(since) >= delay {
+                        told = true;
+                        if tx.send(Some(corner)).is_err() {
+                            break;
+                        }
+                    }
+                }
+            },
+            Err(err) => warn!("Failed to look up the pointer: {}", err),
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// What has been done about the corner the pointer rests in
+#[derive(Debug)]
+pub struct HotCorners {
+    /// Only there when any corners are configured
+    watcher: Option<CornerWatcher>,
+    /// The inhibitor taken by a never corner
+    inhibitor: Option<Cookie>,
+}
+impl HotCorners {
+    /// Start watching the corners the config asks for, if any
+    pub fn new(config: &CornersConfig) -> xidlehook_core::Result<Self> {
+        let watcher = if config.enabled() {
+            Some(CornerWatcher::spawn(config.size, config.delay)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            watcher,
+            inhibitor: None,
+        })
+    }
+
+    /// Wait for the pointer to rest in a corner or leave it, or
+    /// forever if there are no corners. Returns `None` if the watcher
+    /// stopped.
+    pub async fn recv(&mut self) -> Option<Option<Corner>> {
+        match self.watcher {
+            Some(ref mut watcher) => watcher.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Stop watching the corners
+    pub fn stop(&mut self) {
+        self.watcher = None;
+    }
+}
+
+impl App {
+    /// Act on the pointer resting in a corner
+    pub fn enter_corner(&mut self, corner: Corner) -> xidlehook_core::Result<Progress> {
+        let corners = &self.config.corners;
+        if corners.never.contains(&corner) {
+            debug!("The pointer rests in the {} corner, inhibiting", corner);
+            self.hot_corners.inhibitor = Some(self.inhibit.inhibit(
+                format!("The pointer is in the {} corner", corner),
+                String::from("xidlehook"),
+                None,
+            ));
+        } else if corners.lock.contains(&corner) {
+            let id = usize::from(corners.timer);
+            let main_chain = self.main_chain();
+            if id >= main_chain.timers().len() {
+                warn!(
+                    "The lock corners refer to timer {}, which doesn't exist",
+                    id
+                );
+                return Ok(Progress::Continue);
+            }
+            if !main_chain.aborted() && main_chain.next_index() > id {
+                debug!("Timer {} is already active", id);
+                return Ok(Progress::Continue);
+            }
+            info!(
+                "The pointer rests in the {} corner, triggering timer {}",
+                corner, id
+            );
+            let idle = self.idle.get_idle()?;
+            return self.main_chain_mut().trigger(id, idle, true);
+        }
+        Ok(Progress::Continue)
+    }
+
+    /// Forget about the corner the pointer was in, releasing its
+    /// inhibitor if any
+    pub fn leave_corner(&mut self) {
+        if let Some(cookie) = self.hot_corners.inhibitor.take() {
+            debug!("The pointer left the corner, releasing the inhibitor");
+            self.inhibit.uninhibit(cookie);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Serves the freedesktop.org screensaver and power management
+//! inhibition interfaces on the session bus, so that browsers, video
+//! players and video-call applications can keep the chain from
+//! progressing. Their inhibitors end up in the same `Inhibit` module
+//! as the ones taken over the socket.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    future,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use dbus::{
+    channel::MatchingReceiver,
+    message::MatchRule,
+    nonblock::{stdintf::org_freedesktop_dbus::RequestNameReply, SyncConnection},
+};
+use dbus_crossroads::{Context, Crossroads, MethodErr};
+use dbus_tokio::connection::IOResource;
+use log::{debug, trace};
+use tokio::sync::oneshot;
+use xidlehook_core::modules::{inhibit::Cookie, Inhibit};
+
+const SCREENSAVER: &str = "org.freedesktop.ScreenSaver";
+/// Applications disagree on where the screensaver lives, so it's
+/// served on both of the commonly used paths
+const SCREENSAVER_PATHS: &[&str] = &["/ScreenSaver", "/org/freedesktop/ScreenSaver"];
+const POWER_MANAGEMENT: &str = "org.freedesktop.PowerManagement";
+const POWER_MANAGEMENT_INHIBIT: &str = "org.freedesktop.PowerManagement.Inhibit";
+const POWER_MANAGEMENT_PATH: &str = "/org/freedesktop/PowerManagement/Inhibit";
+
+/// The object behind every path. Remembers which bus name took each
+/// inhibitor, so that they can be released if the client goes away
+/// without uninhibiting.
+#[derive(Clone)]
+struct Service {
+    inhibit: Inhibit,
+    senders: Arc<Mutex<HashMap<Cookie, String>>>,
+}
+impl Service {
+    fn senders(&self) -> MutexGuard<'_, HashMap<Cookie, String>> {
+        self.senders.lock().expect("freedesktop: poisoned lock")
+    }
+
+    fn inhibit(&self, ctx: &Context, application: String, reason: String) -> Cookie {
+        let cookie = self.inhibit.inhibit(reason, application, None);
+        if let Some(sender) = ctx.message().sender() {
+            trace!("{} took inhibitor {}", sender, cookie);
+            self.senders().insert(cookie, sender.to_string());
+        }
+        cookie
+    }
+
+    fn uninhibit(&self, cookie: Cookie) -> Result<(), MethodErr> {
+        self.senders().remove(&cookie);
+        if self.inhibit.uninhibit(cookie) {
+            Ok(())
+        } else {
+            Err(MethodErr::failed(&"no such inhibitor"))
+        }
+    }
+
+    /// Release all inhibitors of a client that left the bus
+    fn disconnected(&self, name: &str) {
+        let inhibit = &self.inhibit;
+        self.senders().retain(|&cookie, sender| {
+            if sender != name {
+                return true;
+            }
+            debug!("{} left without releasing inhibitor {}", name, cookie);
+            inhibit.uninhibit(cookie);
+            false
+        });
+    }
+}
+
+/// Own the freedesktop.org names on the session bus and serve
+/// inhibitors into `inhibit`, until the connection is lost. `failed`
+/// is dropped once the names are owned, and otherwise left for the
+/// caller to report failing to start through.
+pub async fn main_loop(
+    inhibit: Inhibit,
+    failed: &mut Option<oneshot::Sender<String>>,
+) -> xidlehook_core::Result<Infallible> {
+    let (resource, conn) = dbus_tokio::connection::new_session_sync()?;
+    run(resource, &conn, inhibit, failed).await
+}
+
+async fn run(
+    resource: IOResource<SyncConnection>,
+    conn: &SyncConnection,
+    inhibit: Inhibit,
+    failed: &mut Option<oneshot::Sender<String>>,
+) -> xidlehook_core::Result<Infallible> {
+    // The connection only makes progress while the resource is polled
+    tokio::select! {
+        res = serve(conn, inhibit, failed) => res,
+        err = resource => Err(format!("lost connection to D-Bus: {}", err).into()),
+    }
+}
+
+async fn serve(
+    conn: &SyncConnection,
+    inhibit: Inhibit,
+    failed: &mut Option<oneshot::Sender<String>>,
+) -> xidlehook_core::Result<Infallible> {
+    let service = Service {
+        inhibit,
+        senders: Arc::default(),
+    };
+
+    let mut cr = Crossroads::new();
+    let screensaver = cr.register(SCREENSAVER, |b| {
+        b.method(
+            "Inhibit",
+            ("application_name", "reason_for_inhibit"),
+            ("cookie",),
+            |ctx, service: &mut Service, (application, reason): (String, String)| {
+                Ok((service.inhibit(ctx, application, reason),))
+            },
+        );
+        b.method(
+            "UnInhibit",
+            ("cookie",),
+            (),
+            |_, service: &mut Service, (cookie,): (Cookie,)| service.uninhibit(cookie),
+        );
+    });
+    let power_management = cr.register(POWER_MANAGEMENT_INHIBIT, |b| {
+        b.method(
+            "Inhibit",
+            ("application", "reason"),
+            ("cookie",),
+            |ctx, service: &mut Service, (application, reason): (String, String)| {
+                Ok((service.inhibit(ctx, application, reason),))
+            },
+        );
+        b.method(
+            "UnInhibit",
+            ("cookie",),
+            (),
+            |_, service: &mut Service, (cookie,): (Cookie,)| service.uninhibit(cookie),
+        );
+        b.method(
+            "HasInhibit",
+            (),
+            ("has_inhibit",),
+            |_, service: &mut Service, (): ()| Ok((service.inhibit.is_inhibited(),)),
+        );
+    });
+    for path in SCREENSAVER_PATHS {
+        cr.insert(*path, &[screensaver], service.clone());
+    }
+    cr.insert(POWER_MANAGEMENT_PATH, &[power_management], service.clone());
+
+    conn.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |msg, conn| {
+            let _ = cr.handle_message(msg, conn);
+            true
+        }),
+    );
+
+    let _name_owner_changed = conn
+        .add_match(MatchRule::new_signal(
+            "org.freedesktop.DBus",
+            "NameOwnerChanged",
+        ))
+        .await?
+        .cb(move |_, (name, _old, new): (String, String, String)| {
+            if new.is_empty() {
+                service.disconnected(&name);
+            }
+            true
+        });
+
+    for &name in &[SCREENSAVER, POWER_MANAGEMENT] {
+        match conn.request_name(name, false, false, true).await? {
+            RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => {
+                debug!("Owning {} on the session bus", name)
+            },
+            _ => return Err(format!("{} is already owned by another program", name).into()),
+        }
+    }
+    failed.take();
+
+    future::pending().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testbus::TestBus;
+
+    use std::time::{Duration, Instant};
+
+    use dbus::nonblock::Proxy;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Serve the interfaces on the bus until they're up
+    async fn start(bus: &TestBus, inhibit: Inhibit) {
+        let (resource, conn) = bus.connect();
+        let (failed_tx, failed_rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let mut failed = Some(failed_tx);
+            let err = run(resource, &conn, inhibit, &mut failed)
+                .await
+                .unwrap_err();
+            if let Some(failed) = failed {
+                let _ = failed.send(err.to_string());
+            }
+        });
+        if let Ok(err) = failed_rx.await {
+            panic!("failed to start: {}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn inhibitors() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let bus = TestBus::start().expect("the D-Bus tests need dbus-daemon");
+        let inhibit = Inhibit::new();
+        start(&bus, inhibit.clone()).await;
+
+        let (resource, client) = bus.connect();
+        let client_task = tokio::spawn(resource);
+        let screensaver = Proxy::new(SCREENSAVER, "/ScreenSaver", TIMEOUT, Arc::clone(&client));
+        let power_management = Proxy::new(
+            POWER_MANAGEMENT,
+            POWER_MANAGEMENT_PATH,
+            TIMEOUT,
+            Arc::clone(&client),
+        );
+
+        let (cookie,): (Cookie,) = screensaver
+            .method_call(SCREENSAVER, "Inhibit", ("firefox", "Playing video"))
+            .await
+            .unwrap();
+        let inhibitors = inhibit.inhibitors();
+        assert_eq!(inhibitors.len(), 1);
+        assert_eq!(inhibitors[0].owner, "firefox");
+        assert_eq!(inhibitors[0].reason, "Playing video");
+        let (has_inhibit,): (bool,) = power_management
+            .method_call(POWER_MANAGEMENT_INHIBIT, "HasInhibit", ())
+            .await
+            .unwrap();
+        assert!(has_inhibit);
+
+        let () = screensaver
+            .method_call(SCREENSAVER, "UnInhibit", (cookie,))
+            .await
+            .unwrap();
+        assert!(!inhibit.is_inhibited());
+        let res: Result<(), _> = screensaver
+            .method_call(SCREENSAVER, "UnInhibit", (cookie,))
+            .await;
+        assert!(res.is_err());
+
+        // Leaving the bus releases the inhibitors
+        let (_cookie,): (Cookie,) = power_management
+            .method_call(POWER_MANAGEMENT_INHIBIT, "Inhibit", ("zoom", "In a call"))
+            .await
+            .unwrap();
+        assert!(inhibit.is_inhibited());
+        client_task.abort();
+        let _ = client_task.await;
+        drop((screensaver, power_management, client));
+
+        let start = Instant::now();
+        while inhibit.is_inhibited() {
+            assert!(start.elapsed() < TIMEOUT, "inhibitor was never released");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn name_taken() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let bus = TestBus::start().expect("the D-Bus tests need dbus-daemon");
+        let (resource, other) = bus.connect();
+        tokio::spawn(resource);
+        other
+            .request_name(SCREENSAVER, false, false, true)
+            .await
+            .unwrap();
+
+        let (resource, conn) = bus.connect();
+        let (failed_tx, _failed_rx) = oneshot::channel();
+        let mut failed = Some(failed_tx);
+        let err = run(resource, &conn, Inhibit::new(), &mut failed)
+            .await
+            .unwrap_err();
+        assert!(failed.is_some());
+        assert!(err.to_string().contains("already owned"));
+    }
+}
@@ -0,0 +1,354 @@
+use super::{Signal, SleepLock};
+use crate::{
+    config::LogindConfig,
+    socket::{ChainEvent, Event},
+};
+
+use std::{convert::Infallible, env, time::Duration};
+
+use dbus::{
+    arg::OwnedFd,
+    message::MatchRule,
+    nonblock::{Proxy, SyncConnection},
+};
+use dbus_tokio::connection::IOResource;
+use futures::StreamExt;
+use log::{debug, trace, warn};
+use tokio::sync::{broadcast, mpsc};
+
+const LOGIND: &str = "org.freedesktop.login1";
+const MANAGER: &str = "org.freedesktop.login1.Manager";
+const MANAGER_PATH: &str = "/org/freedesktop/login1";
+const SESSION: &str = "org.freedesktop.login1.Session";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connect to logind on the system bus and send its signals to the
+/// main loop, until the connection is lost
+pub async fn main_loop(
+    config: LogindConfig,
+    tx: mpsc::Sender<Signal>,
+    events: broadcast::Receiver<ChainEvent>,
+) -> xidlehook_core::Result<Infallible> {
+    let (resource, conn) = dbus_tokio::connection::new_system_sync()?;
+    run(resource, &conn, config, tx, events).await
+}
+
+async fn run(
+    resource: IOResource<SyncConnection>,
+    conn: &SyncConnection,
+    config: LogindConfig,
+    tx: mpsc::Sender<Signal>,
+    events: broadcast::Receiver<ChainEvent>,
+) -> xidlehook_core::Result<Infallible> {
+    // The connection only makes progress while the resource is polled
+    tokio::select! {
+        res = follow(conn, config, tx, events) => res,
+        err = resource => Err(format!("lost connection to D-Bus: {}", err).into()),
+    }
+}
+
+async fn follow(
+    conn: &SyncConnection,
+    config: LogindConfig,
+    tx: mpsc::Sender<Signal>,
+    mut events: broadcast::Receiver<ChainEvent>,
+) -> xidlehook_core::Result<Infallible> {
+    // "auto" is whatever session xidlehook is running in
+    let id = env::var("XDG_SESSION_ID").unwrap_or_else(|_| String::from("auto"));
+    let manager = Proxy::new(LOGIND, MANAGER_PATH, TIMEOUT, conn);
+    let (path,): (dbus::Path<'static>,) = manager.method_call(MANAGER, "GetSession", (id,)).await?;
+    debug!("Following logind session {}", path);
+    let session = Proxy::new(LOGIND, path.clone(), TIMEOUT, conn);
+
+    let (_sleep_match, mut sleep) = conn
+        .add_match(MatchRule::new_signal(MANAGER, "PrepareForSleep").with_sender(LOGIND))
+        .await?
+        .stream::<(bool,)>();
+    let (_lock_match, mut lock) = conn
+        .add_match(
+            MatchRule::new_signal(SESSION, "Lock")
+                .with_sender(LOGIND)
+                .with_path(path.clone()),
+        )
+        .await?
+        .stream::<()>();
+    let (_unlock_match, mut unlock) = conn
+        .add_match(
+            MatchRule::new_signal(SESSION, "Unlock")
+                .with_sender(LOGIND)
+                .with_path(path),
+        )
+        .await?
+        .stream::<()>();
+
+    let mut sleep_lock = match config.sleep_timer {
+        Some(_) => Some(take_sleep_lock(&manager).await?),
+        None => None,
+    };
+
+    let mut idle_hint = false;
+    loop {
+        let signal = tokio::select! {
+            Some((_, (sleeping,))) = sleep.next() => {
+                if !sleeping {
+                    // Take a new lock for the next suspend
+                    if config.sleep_timer.is_some() && sleep_lock.is_none() {
+                        match take_sleep_lock(&manager).await {
+                            Ok(lock) => sleep_lock = Some(lock),
+                            Err(err) => warn!("Failed to delay the next suspend: {}", err),
+                        }
+                    }
+                    Signal::Resumed
+                } else if let Some(lock) = sleep_lock.take() {
+                    Signal::Sleep(lock)
+                } else {
+                    trace!("Going to sleep");
+                    continue;
+                }
+            },
+            Some(_) = lock.next() => Signal::Lock,
+            Some(_) = unlock.next() => Signal::Unlock,
+            event = events.recv() => {
+                let idle = match event {
+                    Ok(event) => match next_idle_hint(&config, event) {
+                        Some(idle) => idle,
+                        None => continue,
+                    },
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => {
+                        return Err("xidlehook stopped sending events".into())
+                    },
+                };
+                if idle != idle_hint {
+                    debug!("Setting the idle hint to {}", idle);
+                    idle_hint = idle;
+                    let res: Result<(), _> =
+                        session.method_call(SESSION, "SetIdleHint", (idle,)).await;
+                    if let Err(err) = res {
+                        warn!("Failed to set the idle hint: {}", err);
+                    }
+                }
+                continue;
+            },
+            else => return Err("logind stopped sending signals".into()),
+        };
+        trace!("Got {:?} from logind", signal);
+        tx.send(signal)
+            .await
+            .map_err(|_| "the main loop stopped listening to logind")?;
+    }
+}
+
+/// Ask logind to hold off suspending until the returned lock is
+/// released
+async fn take_sleep_lock(
+    manager: &Proxy<'_, &SyncConnection>,
+) -> xidlehook_core::Result<SleepLock> {
+    let (fd,): (OwnedFd,) = manager
+        .method_call(
+            MANAGER,
+            "Inhibit",
+            (
+                "sleep",
+                "xidlehook",
+                "Running the sleep timer before suspending",
+                "delay",
+            ),
+        )
+        .await?;
+    Ok(SleepLock(fd.into_fd()))
+}
+
+/// Returns what the idle hint should be after an event, if the event
+/// affects it. Only the main chain is followed.
+fn next_idle_hint(config: &LogindConfig, event: ChainEvent) -> Option<bool> {
+    if event.chain.is_some() {
+        return None;
+    }
+    match event.event {
+        Event::Activated { timer } if Some(timer) == config.idle_hint_timer => Some(true),
+        Event::Active | Event::Reset => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testbus::TestBus;
+
+    use std::{
+        fs::File,
+        io::{self, Read},
+        os::unix::io::FromRawFd,
+        sync::{Arc, Mutex},
+    };
+
+    use dbus::{
+        channel::{MatchingReceiver, Sender},
+        message::Message,
+    };
+    use nix::{
+        fcntl::{self, FcntlArg, OFlag},
+        unistd,
+    };
+
+    const SESSION_PATH: &str = "/org/freedesktop/login1/session/test";
+
+    /// A stand-in for logind, which records the calls made to it
+    #[derive(Default)]
+    struct Logind {
+        /// The read ends of the sleep locks handed out
+        locks: Vec<File>,
+        idle_hints: Vec<bool>,
+    }
+
+    /// Serve a stand-in logind on the bus
+    async fn logind(bus: &TestBus) -> (Arc<SyncConnection>, Arc<Mutex<Logind>>) {
+        let (resource, conn) = bus.connect();
+        tokio::spawn(resource);
+        conn.request_name(LOGIND, false, false, true).await.unwrap();
+
+        let state = Arc::new(Mutex::new(Logind::default()));
+        let handler = Arc::clone(&state);
+        conn.start_receive(
+            MatchRule::new_method_call(),
+            Box::new(move |msg, conn| {
+                let mut state = handler.lock().unwrap();
+                let reply = match msg.member().as_deref() {
+                    Some("GetSession") => {
+                        msg.method_return().append1(dbus::Path::from(SESSION_PATH))
+                    },
+                    Some("Inhibit") => {
+                        let (read, write) = unistd::pipe().unwrap();
+                        fcntl::fcntl(read, FcntlArg::F_SETFL(OFlag::O_NONBLOCK)).unwrap();
+                        state.locks.push(unsafe { File::from_raw_fd(read) });
+                        msg.method_return().append1(unsafe { OwnedFd::new(write) })
+                    },
+                    Some("SetIdleHint") => {
+                        state.idle_hints.push(msg.read1().unwrap());
+                        msg.method_return()
+                    },
+                    _ => return true,
+                };
+                let _ = conn.send(reply);
+                true
+            }),
+        );
+        (conn, state)
+    }
+
+    /// Returns true once every copy of the sleep lock is closed
+    fn released(lock: &mut File) -> bool {
+        match lock.read(&mut [0]) {
+            Ok(0) => true,
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => false,
+            res => panic!("unexpected read from the sleep lock: {:?}", res),
+        }
+    }
+
+    async fn recv(rx: &mut mpsc::Receiver<Signal>) -> Signal {
+        tokio::time::timeout(TIMEOUT, rx.recv())
+            .await
+            .expect("no signal arrived")
+            .expect("stopped following logind")
+    }
+
+    #[tokio::test]
+    async fn signals() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let bus = TestBus::start().expect("the D-Bus tests need dbus-daemon");
+        let (logind, state) = logind(&bus).await;
+
+        let config = LogindConfig {
+            lock_timer: Some(0),
+            idle_hint_timer: Some(1),
+            sleep_timer: Some(2),
+        };
+        let (tx, mut rx) = mpsc::channel(4);
+        let (events_tx, events) = broadcast::channel(4);
+        let (resource, conn) = bus.connect();
+        tokio::spawn(async move {
+            let err = run(resource, &conn, config, tx, events).await.unwrap_err();
+            debug!("Stopped following logind: {}", err);
+        });
+
+        // Nothing tells when the matches are in place, other than
+        // the sleep lock being taken after them
+        let start = std::time::Instant::now();
+        while state.lock().unwrap().locks.is_empty() {
+            assert!(start.elapsed() < TIMEOUT, "no sleep lock was taken");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Going to sleep hands over the lock
+        logind
+            .send(
+                Message::new_signal(MANAGER_PATH, MANAGER, "PrepareForSleep")
+                    .unwrap()
+                    .append1(true),
+            )
+            .unwrap();
+        let lock = match recv(&mut rx).await {
+            Signal::Sleep(lock) => lock,
+            signal => panic!("expected to sleep, got {:?}", signal),
+        };
+        assert!(!released(&mut state.lock().unwrap().locks[0]));
+        drop(lock);
+        assert!(released(&mut state.lock().unwrap().locks[0]));
+
+        // Waking up takes a new one
+        logind
+            .send(
+                Message::new_signal(MANAGER_PATH, MANAGER, "PrepareForSleep")
+                    .unwrap()
+                    .append1(false),
+            )
+            .unwrap();
+        assert!(matches!(recv(&mut rx).await, Signal::Resumed));
+        assert_eq!(state.lock().unwrap().locks.len(), 2);
+
+        logind
+            .send(Message::new_signal(SESSION_PATH, SESSION, "Lock").unwrap())
+            .unwrap();
+        assert!(matches!(recv(&mut rx).await, Signal::Lock));
+        logind
+            .send(Message::new_signal(SESSION_PATH, SESSION, "Unlock").unwrap())
+            .unwrap();
+        assert!(matches!(recv(&mut rx).await, Signal::Unlock));
+
+        // Other sessions are ignored
+        logind
+            .send(
+                Message::new_signal("/org/freedesktop/login1/session/other", SESSION, "Lock")
+                    .unwrap(),
+            )
+            .unwrap();
+        logind
+            .send(Message::new_signal(SESSION_PATH, SESSION, "Unlock").unwrap())
+            .unwrap();
+        assert!(matches!(recv(&mut rx).await, Signal::Unlock));
+
+        // The idle hint follows the main chain
+        let send = |chain: Option<&str>, event| {
+            events_tx
+                .send(ChainEvent {
+                    chain: chain.map(String::from),
+                    event,
+                })
+                .unwrap();
+        };
+        send(None, Event::Activated { timer: 0 });
+        send(Some("other"), Event::Activated { timer: 1 });
+        send(None, Event::Activated { timer: 1 });
+        send(None, Event::Activated { timer: 2 });
+        send(None, Event::Active);
+        let start = std::time::Instant::now();
+        while state.lock().unwrap().idle_hints.len() < 2 {
+            assert!(start.elapsed() < TIMEOUT, "the idle hint was never set");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(state.lock().unwrap().idle_hints, [true, false]);
+    }
+}
@@ -0,0 +1,73 @@
+use super::Signal;
+use crate::{config::LogindConfig, socket::TimerId, App};
+
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use log::{debug, info, warn};
+use xidlehook_core::Progress;
+
+impl App {
+    pub fn handle_logind(&mut self, signal: Signal) -> xidlehook_core::Result<Progress> {
+        match signal {
+            Signal::Sleep(lock) => {
+                let id = match self.logind_timer(|logind| logind.sleep_timer) {
+                    Some(id) => id,
+                    None => return Ok(Progress::Continue),
+                };
+                let main_chain = self.main_chain();
+                if !main_chain.aborted() && main_chain.next_index() > id {
+                    debug!("Timer {} is already active, suspending right away", id);
+                    return Ok(Progress::Continue);
+                }
+                info!("Going to sleep, triggering timer {}", id);
+
+                let idle = self.idle.get_idle()?;
+                let inherited = lock.inheritable()?;
+                self.set_sleep_lock(id, Some(inherited.as_raw_fd()));
+                let progress = self.main_chain_mut().trigger(id, idle, true);
+                self.set_sleep_lock(id, None);
+
+                // From here on, only the activation command holds on
+                // to the lock, and releases it by closing it or exiting
+                drop((lock, inherited));
+                return progress;
+            },
+            Signal::Resumed => {
+                info!("Resumed from suspend, resetting the chains");
+                self.chains.reset(self.idle.get_idle()?)?;
+            },
+            Signal::Lock => {
+                if let Some(id) = self.logind_timer(|logind| logind.lock_timer) {
+                    info!("The session was locked, triggering timer {}", id);
+                    let idle = self.idle.get_idle()?;
+                    return self.main_chain_mut().trigger(id, idle, true);
+                }
+            },
+            Signal::Unlock => {
+                info!("The session was unlocked, starting over");
+                self.chains.reset(self.idle.get_idle()?)?;
+            },
+        }
+        Ok(Progress::Continue)
+    }
+
+    /// Returns the index of a timer from the logind options, if it's
+    /// set and exists
+    fn logind_timer<F>(&self, pick: F) -> Option<usize>
+    where
+        F: FnOnce(&LogindConfig) -> Option<TimerId>,
+    {
+        let id = usize::from(self.config.logind.as_ref().and_then(pick)?);
+        if id >= self.main_chain().timers().len() {
+            warn!("logind refers to timer {}, which doesn't exist", id);
+            return None;
+        }
+        Some(id)
+    }
+
+    fn set_sleep_lock(&mut self, id: usize, fd: Option<RawFd>) {
+        if let Some(timer) = self.main_chain_mut().timer_mut(id) {
+            timer.set_sleep_lock(fd);
+        }
+    }
+}
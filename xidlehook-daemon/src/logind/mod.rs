@@ -0,0 +1,99 @@
+//! Follows the current session through systemd-logind: resuming from
+//! suspend resets the chains, `loginctl lock-session` can trigger a
+//! timer, `loginctl unlock-session` starts the chains over, and the
+//! session's idle hint is set while the chain is past a chosen timer.
+//!
+//! A timer can also be triggered before suspending. logind waits for
+//! it by means of a delay inhibitor, which is handed to the activation
+//! command so that it can tell when the screen is locked, just like
+//! xss-lock's `--transfer-sleep-lock`.
+
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use nix::unistd;
+
+#[cfg(feature = "logind")]
+mod bus;
+pub mod handler;
+
+#[cfg(feature = "logind")]
+pub use self::bus::main_loop;
+
+/// Something logind told us about, which the main loop acts on
+#[derive(Debug)]
+#[cfg_attr(not(feature = "logind"), allow(dead_code))]
+pub enum Signal {
+    /// The computer is about to suspend, as soon as the lock is
+    /// released
+    Sleep(SleepLock),
+    /// The computer woke up from a suspend
+    Resumed,
+    /// The session should be locked
+    Lock,
+    /// The session should be unlocked
+    Unlock,
+}
+
+/// A logind delay inhibitor, which holds off suspending until every
+/// copy of it is closed
+#[derive(Debug)]
+pub struct SleepLock(RawFd);
+impl SleepLock {
+    /// Returns a copy of the lock which child processes inherit
+    pub fn inheritable(&self) -> nix::Result<Self> {
+        // Unlike the lock from D-Bus, duplicates aren't close-on-exec
+        unistd::dup(self.0).map(SleepLock)
+    }
+}
+impl AsRawFd for SleepLock {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+impl Drop for SleepLock {
+    fn drop(&mut self) {
+        let _ = unistd::close(self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timers::CmdTimer;
+
+    use std::{fs::File, io::Read, os::unix::io::FromRawFd, time::Duration};
+
+    use nix::fcntl::OFlag;
+    use xidlehook_core::Timer;
+
+    #[test]
+    fn activation_gets_the_sleep_lock() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // Like the lock from D-Bus, the original is close-on-exec
+        let (read, write) = unistd::pipe2(OFlag::O_CLOEXEC).unwrap();
+        let mut read = unsafe { File::from_raw_fd(read) };
+        let lock = SleepLock(write);
+
+        let mut timer = CmdTimer::from_parts(
+            Duration::default(),
+            vec![
+                String::from("sh"),
+                String::from("-c"),
+                String::from("echo locked >&\"$XIDLEHOOK_SLEEP_LOCK_FD\""),
+            ],
+            Vec::new(),
+            Vec::new(),
+        );
+        let inherited = lock.inheritable().unwrap();
+        timer.set_sleep_lock(Some(inherited.as_raw_fd()));
+        timer.activate().unwrap();
+        timer.set_sleep_lock(None);
+        drop((lock, inherited));
+
+        // The pipe is only closed once the command is done with it
+        let mut output = String::new();
+        read.read_to_string(&mut output).unwrap();
+        assert_eq!(output, "locked\n");
+    }
+}
@@ -13,24 +13,57 @@
     clippy::integer_division,
 )]
 
-use std::{fs, rc::Rc, time::Duration};
+use std::{
+    env, fs, mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
+};
 
-use log::{trace, warn};
+use futures::StreamExt;
+use log::{debug, info, trace, warn};
 use nix::sys::wait;
-use structopt::StructOpt;
+use serde::Deserialize;
+use structopt::{clap::arg_enum, StructOpt};
 use tokio::{
     signal::unix::{signal, SignalKind},
-    sync::mpsc,
+    sync::{broadcast, mpsc, oneshot},
 };
 use xidlehook_core::{
-    modules::{StopAt, Xcb},
-    Module, Xidlehook,
+    modules::{
+        xcb::{Corner, FullscreenMode, OutputSelector, WindowRule},
+        Inhibit, StopAt, Xcb,
+    },
+    Chains, IdleSource, Progress, Xidlehook,
 };
 
+mod config;
+mod corners;
+#[cfg(feature = "freedesktop")]
+mod freedesktop;
+mod logind;
+mod named;
+mod power;
 mod socket;
+mod state;
+#[cfg(all(test, any(feature = "freedesktop", feature = "logind")))]
+mod testbus;
 mod timers;
 
-use self::timers::CmdTimer;
+use self::{
+    config::{Config, TimerConfig},
+    corners::HotCorners,
+    named::Named,
+    power::Profile,
+    socket::TimerId,
+    state::Changes,
+    timers::CmdTimer,
+};
+
+/// The name of the chain made of the `[[timer]]`s. It's the one the
+/// socket addresses by default, and the only one that logind, the
+/// corners and the power profiles act on.
+pub const MAIN_CHAIN: &str = "main";
 
 struct Defer<F: FnMut()>(F);
 impl<F: FnMut()> Drop for Defer<F> {
@@ -39,6 +72,25 @@ impl<F: FnMut()> Drop for Defer<F> {
     }
 }
 
+arg_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Backend {
+        Auto,
+        X11,
+        Wayland,
+        Evdev,
+    }
+}
+
+arg_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum AudioBackend {
+        Pulse,
+        Pipewire,
+    }
+}
+
 #[derive(StructOpt, Debug)]
 pub struct Opt {
     /// Print the idle time to standard output. This is similar to xprintidle.
@@ -53,12 +105,65 @@ pub struct Opt {
     /// videos.
     #[structopt(long, conflicts_with("print"))]
     pub not_when_fullscreen: bool,
+    /// Which windows --not-when-fullscreen looks at: any window,
+    /// visible ones on the current desktop, or only the focused one
+    #[structopt(long, conflicts_with("print"), possible_values = &["any", "visible", "focused"])]
+    pub fullscreen_mode: Option<FullscreenMode>,
+    /// Only let --not-when-fullscreen look at windows on this output,
+    /// such as "HDMI-1", or "primary" for the primary output. Can be
+    /// given several times, and defaults to all outputs.
+    #[structopt(long, conflicts_with("print"), number_of_values = 1)]
+    pub fullscreen_output: Vec<OutputSelector>,
+    /// Don't invoke the timer when any visible window matches this
+    /// rule, such as "class=zoom", "title=meeting" or "role=call". The
+    /// value must be part of the window's class, title or role,
+    /// ignoring case.
+    #[structopt(long, conflicts_with("print"), number_of_values = 1)]
+    pub not_when_window: Vec<WindowRule>,
+    /// Like --not-when-window, but only looks at the focused window
+    #[structopt(long, conflicts_with("print"), number_of_values = 1)]
+    pub not_when_focused_window: Vec<WindowRule>,
+    /// Trigger a timer (see --corner-timer) when the pointer rests in
+    /// this corner of any monitor: top-left, top-right, bottom-left or
+    /// bottom-right. Can be given several times.
+    #[structopt(long, conflicts_with("print"), number_of_values = 1)]
+    pub lock_corner: Vec<Corner>,
+    /// Don't invoke the timer while the pointer rests in this corner
+    /// of any monitor. Can be given several times.
+    #[structopt(long, conflicts_with("print"), number_of_values = 1)]
+    pub never_corner: Vec<Corner>,
+    /// Which timer --lock-corner triggers, counting from 0. Defaults
+    /// to the first one.
+    #[structopt(long, conflicts_with("print"))]
+    pub corner_timer: Option<TimerId>,
+    /// How long the pointer has to rest in a corner before it counts.
+    /// Defaults to 5s.
+    #[structopt(long, conflicts_with("print"), parse(try_from_str = xidlehook_core::duration::parse))]
+    pub corner_delay: Option<Duration>,
+    /// The size of the corners, in pixels. Defaults to 10.
+    #[structopt(long, conflicts_with("print"))]
+    pub corner_size: Option<u16>,
     /// Detect when the system wakes up from a suspend and reset the idle timer
     #[structopt(long, conflicts_with("print"))]
     pub detect_sleep: bool,
+    /// Where to get the idle time from. Auto uses Wayland if
+    /// WAYLAND_DISPLAY is set, X11 if DISPLAY is set, and otherwise
+    /// reads the input devices directly (evdev).
+    #[structopt(long, default_value = "auto", possible_values = &Backend::variants(), case_insensitive = true)]
+    pub backend: Backend,
+    /// How long the user has to be idle before a Wayland compositor
+    /// reports it, such as "500ms". The idle time reads as zero until
+    /// then. Defaults to one second.
+    #[structopt(long, conflicts_with("print"), parse(try_from_str = xidlehook_core::duration::parse))]
+    pub wayland_timeout: Option<Duration>,
+    /// Only watch these input devices when using the evdev backend,
+    /// instead of all of /dev/input/event*
+    #[structopt(long, conflicts_with("print"))]
+    pub evdev_device: Vec<PathBuf>,
 
-    /// The duration is the number of seconds of inactivity which
-    /// should trigger this timer.
+    /// The duration is the amount of inactivity which should trigger
+    /// this timer, such as "90s", "5m" or "1h30m". A bare number is a
+    /// number of seconds.
     ///
     /// The command is what is invoked when the idle duration is
     /// reached. It's passed through \"/bin/sh -c\".
@@ -66,18 +171,101 @@ pub struct Opt {
     /// The canceller is what is invoked when the user becomes active
     /// after the timer has gone off, but before the next timer (if
     /// any). Pass an empty string to not have one.
-    #[structopt(long, conflicts_with("print"), required_unless("print"), value_names = &["duration", "command", "canceller"])]
+    ///
+    /// Timers given here replace the ones in the config file.
+    #[structopt(long, conflicts_with("print"), value_names = &["duration", "command", "canceller"])]
     pub timer: Vec<String>,
 
+    /// Read timers and options from this TOML file. Defaults to
+    /// $XDG_CONFIG_HOME/xidlehook/config.toml, if it exists.
+    #[structopt(long, conflicts_with("print"))]
+    pub config: Option<PathBuf>,
+    /// Reload the config file as soon as it changes, not only on
+    /// SIGHUP. Without a config file, the default one is watched for
+    /// being created.
+    #[structopt(long, conflicts_with("print"))]
+    pub watch_config: bool,
+
     /// Listen to a unix socket at this address for events.
     /// Each event is one line of JSON data.
     #[structopt(long, conflicts_with("print"))]
     pub socket: Option<String>,
+    /// Save the changes made to the timers over the socket, and bring
+    /// them back after restarting. They're saved to
+    /// $XDG_STATE_HOME/xidlehook/state.json unless --state-file is
+    /// given.
+    #[structopt(long, conflicts_with("print"))]
+    pub persist: bool,
+    /// Where --persist saves the changes
+    #[structopt(long, conflicts_with("print"))]
+    pub state_file: Option<PathBuf>,
 
-    /// Don't invoke the timer when any audio is playing (PulseAudio specific)
-    #[cfg(feature = "pulse")]
+    /// Own org.freedesktop.ScreenSaver and
+    /// org.freedesktop.PowerManagement on the session bus, so that
+    /// applications such as browsers and video players can inhibit
+    /// the timers
+    #[cfg(feature = "freedesktop")]
+    #[structopt(long, conflicts_with("print"))]
+    pub freedesktop: bool,
+    /// Follow the session through systemd-logind, resetting the
+    /// timers when resuming from suspend. See the config file for
+    /// reacting to lock-session and setting the idle hint.
+    #[cfg(feature = "logind")]
+    #[structopt(long, conflicts_with("print"))]
+    pub logind: bool,
+
+    /// Don't invoke the timer when any audio is playing
+    #[cfg(any(feature = "pulse", feature = "pipewire"))]
     #[structopt(long, conflicts_with("print"))]
     pub not_when_audio: bool,
+    /// Which sound server --not-when-audio talks to. Defaults to
+    /// PipeWire if xidlehook was built with it and it's running, and
+    /// PulseAudio otherwise.
+    #[cfg(any(feature = "pulse", feature = "pipewire"))]
+    #[structopt(long, conflicts_with("print"), possible_values = &AudioBackend::variants(), case_insensitive = true)]
+    pub audio_backend: Option<AudioBackend>,
+    /// Don't let streams from this application keep the timers from
+    /// running, such as a notification daemon. The value must be part
+    /// of the application's name or binary, ignoring case.
+    #[cfg(any(feature = "pulse", feature = "pipewire"))]
+    #[structopt(long, conflicts_with("print"), number_of_values = 1)]
+    pub audio_ignore: Vec<String>,
+    /// Don't let muted streams keep the timers from running
+    /// (PulseAudio specific)
+    #[cfg(any(feature = "pulse", feature = "pipewire"))]
+    #[structopt(long, conflicts_with("print"))]
+    pub audio_ignore_muted: bool,
+    /// Only count streams playing to this sink, by name (PulseAudio
+    /// specific). Can be given several times.
+    #[cfg(any(feature = "pulse", feature = "pipewire"))]
+    #[structopt(long, conflicts_with("print"), number_of_values = 1)]
+    pub audio_sink: Vec<String>,
+    /// Also count recording streams, such as a microphone in use
+    /// during a call. Recording what's being played doesn't count.
+    #[cfg(any(feature = "pulse", feature = "pipewire"))]
+    #[structopt(long, conflicts_with("print"))]
+    pub audio_capture: bool,
+    /// Don't invoke the timer while a microphone is in use, such as
+    /// during a call
+    #[cfg(any(feature = "pulse", feature = "pipewire"))]
+    #[structopt(long, conflicts_with("print"))]
+    pub not_when_microphone: bool,
+    /// Don't invoke the timer while any process holds a camera
+    /// (/dev/video*) open
+    #[cfg(target_os = "linux")]
+    #[structopt(long, conflicts_with("print"))]
+    pub not_when_camera: bool,
+    /// Don't invoke the timer while a matching process is running.
+    /// The regex is matched against the process name, or against the
+    /// whole command line when prefixed with "cmdline=". Can be given
+    /// several times.
+    #[cfg(target_os = "linux")]
+    #[structopt(long, conflicts_with("print"), number_of_values = 1)]
+    pub not_when_process: Vec<xidlehook_core::modules::process::ProcessPattern>,
+    /// Only let --not-when-process match your own processes
+    #[cfg(target_os = "linux")]
+    #[structopt(long, conflicts_with("print"))]
+    pub process_own_only: bool,
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -86,73 +274,351 @@ async fn main() -> xidlehook_core::Result<()> {
 
     let opt = Opt::from_args();
 
-    let xcb = Rc::new(Xcb::new()?);
+    let backend = match opt.backend {
+        Backend::Auto if env::var_os("WAYLAND_DISPLAY").is_some() => Backend::Wayland,
+        Backend::Auto if env::var_os("DISPLAY").is_some() => Backend::X11,
+        Backend::Auto => Backend::Evdev,
+        backend => backend,
+    };
+
+    if opt.print && backend == Backend::Wayland {
+        return Err(
+            "--print isn't supported on Wayland, as compositors only report when the user \
+             becomes idle, not for how long they have been. Under XWayland, try --backend x11."
+                .into(),
+        );
+    }
+    if opt.print && backend == Backend::Evdev {
+        return Err(
+            "--print isn't supported with evdev, as it only counts the idle time since \
+             xidlehook started. Try --backend x11."
+                .into(),
+        );
+    }
+
+    // Without X11 as the backend, an X connection is still useful for
+    // (e.g. XWayland) windows, but not required
+    let xcb = match Xcb::new() {
+        Ok(mut xcb) => {
+            if backend == Backend::X11 {
+                if let Err(err) = xcb.enable_idle_alarms() {
+                    warn!("Falling back to polling the idle time: {}", err);
+                }
+            }
+            Some(Rc::new(xcb))
+        },
+        Err(err) if backend != Backend::X11 => {
+            debug!("No X connection, continuing without one: {}", err);
+            None
+        },
+        Err(err) => return Err(err),
+    };
+
+    let idle: Rc<dyn IdleSource> = match (backend, &xcb) {
+        (Backend::X11, Some(xcb)) => Rc::clone(xcb) as Rc<dyn IdleSource>,
+        (Backend::Wayland, _) => wayland(opt.wayland_timeout)?,
+        _ => evdev(&opt.evdev_device)?,
+    };
 
     if opt.print {
-        let idle = xcb.get_idle()?;
+        let idle = idle.get_idle()?;
         println!("{}", idle.as_millis());
         return Ok(());
     }
 
-    let mut timers = Vec::new();
-    let mut iter = opt.timer.iter().peekable();
-    while iter.peek().is_some() {
-        // clap-rs will ensure there are always a multiple of 3 arguments
-        let duration: u64 = match iter.next().unwrap().parse() {
-            Ok(duration) => duration,
-            Err(err) => {
-                eprintln!("error: failed to parse duration as number: {}", err);
-                return Ok(());
-            },
-        };
-        timers.push(CmdTimer::from_shell(
-            Duration::from_secs(duration),
-            iter.next().unwrap().into(),
-            iter.next().unwrap().into(),
-            String::new(),
+    let config = Config::load(&opt)?;
+
+    let mut app = App::new(opt, config, xcb, idle)?;
+    app.restore_changes()?;
+    app.main_loop().await
+}
+
+/// Create a chain of timers with the modules that the config asks for
+fn chain(
+    config: &Config,
+    timers: &[TimerConfig],
+    modules: Vec<Named>,
+    broadcast: socket::Broadcast,
+) -> Chain {
+    Xidlehook::new(timers.iter().map(TimerConfig::to_timer).collect())
+        .register(broadcast)
+        .register(modules)
+        .with_detect_sleep(config.detect_sleep)
+}
+
+/// Create the `[[chain]]`s, each with its own modules
+fn other_chains(
+    config: &Config,
+    xcb: Option<&Rc<Xcb>>,
+    inhibit: &Inhibit,
+    events: &broadcast::Sender<socket::ChainEvent>,
+) -> xidlehook_core::Result<Vec<(String, Chain)>> {
+    config
+        .chains
+        .iter()
+        .map(|other| {
+            let chain = chain(
+                config,
+                &other.timers,
+                modules(config, xcb, inhibit, false)?,
+                socket::Broadcast {
+                    events: events.clone(),
+                    chain: Some(other.name.clone()),
+                },
+            );
+            Ok((other.name.clone(), chain))
+        })
+        .collect()
+}
+
+/// Create the modules that the config asks for. Only the main chain
+/// makes xidlehook exit with `once`.
+fn modules(
+    config: &Config,
+    xcb: Option<&Rc<Xcb>>,
+    inhibit: &Inhibit,
+    main: bool,
+) -> xidlehook_core::Result<Vec<Named>> {
+    // Inhibitors are shared, so that they survive reloading the config
+    // and keep all chains from running
+    let mut modules = vec![Named::new("inhibit", inhibit.clone())];
+
+    if config.once && main {
+        modules.push(Named::new("once", StopAt::completion()));
+    }
+    if config.corners.enabled() && xcb.is_none() {
+        return Err("corners require an X connection".into());
+    }
+    if config.not_when_fullscreen {
+        let xcb = xcb.ok_or("not_when_fullscreen requires an X connection")?;
+        modules.push(Named::new(
+            "not_when_fullscreen",
+            Rc::clone(xcb)
+                .not_when_fullscreen()
+                .with_mode(config.fullscreen_mode)
+                .with_outputs(config.fullscreen_outputs.clone()),
+        ));
+    }
+    if !config.not_when_window.is_empty() {
+        let xcb = xcb.ok_or("not_when_window requires an X connection")?;
+        modules.push(Named::new(
+            "not_when_window",
+            Rc::clone(xcb).not_when_window(config.not_when_window.clone()),
+        ));
+    }
+    if !config.not_when_focused_window.is_empty() {
+        let xcb = xcb.ok_or("not_when_focused_window requires an X connection")?;
+        modules.push(Named::new(
+            "not_when_focused_window",
+            Rc::clone(xcb).not_when_focused_window(config.not_when_focused_window.clone()),
+        ));
+    }
+    if config.not_when_audio {
+        #[cfg(any(feature = "pulse", feature = "pipewire"))]
+        modules.push(Named::new(
+            "not_when_audio",
+            not_when_audio(config.audio_backend, config.audio_filter())?,
+        ));
+        #[cfg(not(any(feature = "pulse", feature = "pipewire")))]
+        return Err("xidlehook was built without pulse or pipewire support".into());
+    }
+    if config.not_when_microphone {
+        #[cfg(any(feature = "pulse", feature = "pipewire"))]
+        modules.push(Named::new(
+            "not_when_microphone",
+            not_when_audio(
+                config.audio_backend,
+                config.audio_filter().with_capture_only(true),
+            )?,
+        ));
+        #[cfg(not(any(feature = "pulse", feature = "pipewire")))]
+        return Err("xidlehook was built without pulse or pipewire support".into());
+    }
+    if config.not_when_camera {
+        #[cfg(target_os = "linux")]
+        modules.push(Named::new(
+            "not_when_camera",
+            xidlehook_core::modules::NotWhenCamera::new(),
         ));
+        #[cfg(not(target_os = "linux"))]
+        return Err("not_when_camera is only supported on Linux".into());
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if !config.not_when_process.is_empty() {
+            let uid = if config.process_own_only {
+                Some(nix::unistd::getuid().as_raw())
+            } else {
+                None
+            };
+            modules.push(Named::new(
+                "not_when_process",
+                xidlehook_core::modules::NotWhenProcess::new(config.not_when_process.clone())
+                    .with_user(uid),
+            ));
+        }
     }
 
-    let mut modules: Vec<Box<dyn Module>> = Vec::new();
+    Ok(modules)
+}
 
-    if opt.once {
-        modules.push(Box::new(StopAt::completion()));
-    }
-    if opt.not_when_fullscreen {
-        modules.push(Box::new(Rc::clone(&xcb).not_when_fullscreen()));
+/// Connect to the sound server, preferring PipeWire unless a backend
+/// was picked
+#[cfg(any(feature = "pulse", feature = "pipewire"))]
+fn not_when_audio(
+    backend: Option<AudioBackend>,
+    filter: xidlehook_core::modules::AudioFilter,
+) -> xidlehook_core::Result<Box<dyn xidlehook_core::Module>> {
+    #[cfg(feature = "pipewire")]
+    {
+        if backend != Some(AudioBackend::Pulse) {
+            match xidlehook_core::modules::pipewire::NotWhenAudio::with_filter(filter.clone()) {
+                Ok(module) => return Ok(Box::new(module)),
+                Err(err) if backend.is_none() && cfg!(feature = "pulse") => {
+                    debug!("Falling back to PulseAudio: {}", err);
+                },
+                Err(err) => return Err(err),
+            }
+        }
     }
     #[cfg(feature = "pulse")]
     {
-        if opt.not_when_audio {
-            modules.push(Box::new(xidlehook_core::modules::NotWhenAudio::new()?))
+        if backend != Some(AudioBackend::Pipewire) {
+            return Ok(Box::new(
+                xidlehook_core::modules::NotWhenAudio::with_filter(filter)?,
+            ));
         }
     }
+    match backend {
+        Some(AudioBackend::Pipewire) => Err("xidlehook was built without pipewire support".into()),
+        _ => Err("xidlehook was built without pulse support".into()),
+    }
+}
 
-    let xidlehook = Xidlehook::new(timers)
-        .register(modules)
-        .with_detect_sleep(opt.detect_sleep);
-    App {
-        opt,
-        xcb,
-        xidlehook,
+#[cfg(target_os = "linux")]
+fn watch_config(path: &Path) -> xidlehook_core::Result<inotify::EventStream<Vec<u8>>> {
+    use inotify::{Inotify, WatchMask};
+
+    // Watch the directory, as editors often replace the file rather
+    // than writing to it
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut inotify = Inotify::init()?;
+    inotify.add_watch(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)?;
+    Ok(inotify.event_stream(vec![0; 1024])?)
+}
+
+#[cfg(feature = "wayland")]
+fn wayland(timeout: Option<Duration>) -> xidlehook_core::Result<Rc<dyn IdleSource>> {
+    use xidlehook_core::modules::Wayland;
+
+    let wayland = match timeout {
+        Some(timeout) => Wayland::with_timeout(timeout)?,
+        None => Wayland::new()?,
+    };
+    Ok(Rc::new(wayland))
+}
+#[cfg(not(feature = "wayland"))]
+fn wayland(_timeout: Option<Duration>) -> xidlehook_core::Result<Rc<dyn IdleSource>> {
+    Err("xidlehook was built without wayland support".into())
+}
+
+#[cfg(target_os = "linux")]
+fn evdev(devices: &[PathBuf]) -> xidlehook_core::Result<Rc<dyn IdleSource>> {
+    use xidlehook_core::modules::Evdev;
+
+    if devices.is_empty() {
+        Ok(Rc::new(Evdev::new()?))
+    } else {
+        Ok(Rc::new(Evdev::from_paths(devices.iter().cloned())?))
     }
-    .main_loop()
-    .await
 }
+#[cfg(not(target_os = "linux"))]
+fn evdev(_devices: &[PathBuf]) -> xidlehook_core::Result<Rc<dyn IdleSource>> {
+    Err("the evdev backend is only available on Linux".into())
+}
+
+/// The built-in modules, followed by the ones the config asks for
+type Modules = (((), socket::Broadcast), Vec<Named>);
+type Chain = Xidlehook<CmdTimer, Modules>;
 
 struct App {
     opt: Opt,
-    xcb: Rc<Xcb>,
-    xidlehook: Xidlehook<CmdTimer, ((), Vec<Box<dyn Module>>)>,
+    config: Config,
+    xcb: Option<Rc<Xcb>>,
+    idle: Rc<dyn IdleSource>,
+    inhibit: Inhibit,
+    hot_corners: HotCorners,
+    /// The power profile whose timers are in use
+    profile: Option<Profile>,
+    events: broadcast::Sender<socket::ChainEvent>,
+    chains: Chains<CmdTimer, Modules>,
+    /// The changes made to the timers over the socket
+    changes: Changes,
 }
 impl App {
+    /// Set up the chains and everything else the config asks for
+    fn new(
+        opt: Opt,
+        config: Config,
+        xcb: Option<Rc<Xcb>>,
+        idle: Rc<dyn IdleSource>,
+    ) -> xidlehook_core::Result<Self> {
+        let profile = power::initial_profile(&config);
+        let inhibit = Inhibit::new();
+        let (events, _) = broadcast::channel(16);
+
+        let main_chain = chain(
+            &config,
+            config.timers(profile),
+            modules(&config, xcb.as_ref(), &inhibit, true)?,
+            socket::Broadcast {
+                events: events.clone(),
+                chain: None,
+            },
+        );
+        let mut chains = Chains::new().with_chain(MAIN_CHAIN, main_chain);
+        for (name, chain) in other_chains(&config, xcb.as_ref(), &inhibit, &events)? {
+            chains.insert(name, chain);
+        }
+        let changes = Changes::load(config.state_path());
+        let hot_corners = HotCorners::new(&config.corners)?;
+        Ok(Self {
+            opt,
+            config,
+            xcb,
+            idle,
+            inhibit,
+            hot_corners,
+            profile,
+            events,
+            chains,
+            changes,
+        })
+    }
+
+    fn main_chain(&self) -> &Chain {
+        self.chains
+            .get(MAIN_CHAIN)
+            .expect("the main chain is never removed")
+    }
+
+    fn main_chain_mut(&mut self) -> &mut Chain {
+        self.chains
+            .get_mut(MAIN_CHAIN)
+            .expect("the main chain is never removed")
+    }
+
     async fn main_loop(&mut self) -> xidlehook_core::Result<()> {
         let (socket_tx, socket_rx) = mpsc::channel(4);
-        let _scope = if let Some(address) = self.opt.socket.clone() {
+        let _scope = if let Some(address) = self.config.socket.clone() {
             {
                 let address = address.clone();
+                let events = self.events.clone();
                 tokio::spawn(async move {
-                    if let Err(err) = socket::main_loop(&address, socket_tx).await {
+                    if let Err(err) = socket::main_loop(&address, socket_tx, events).await {
                         warn!("Socket handling errored: {}", err);
                     }
                 });
@@ -167,8 +633,88 @@ impl App {
 
         let mut socket_rx = Some(socket_rx);
 
+        if self.config.freedesktop {
+            #[cfg(feature = "freedesktop")]
+            {
+                let inhibit = self.inhibit.clone();
+                let (failed_tx, failed_rx) = oneshot::channel();
+                tokio::spawn(async move {
+                    let mut failed = Some(failed_tx);
+                    let err = freedesktop::main_loop(inhibit, &mut failed)
+                        .await
+                        .unwrap_err();
+                    match failed {
+                        Some(failed) => {
+                            let _ = failed.send(err.to_string());
+                        },
+                        None => warn!("D-Bus handling errored: {}", err),
+                    }
+                });
+                // Another screensaver owning the names is worth
+                // refusing to start over
+                if let Ok(err) = failed_rx.await {
+                    return Err(
+                        format!("failed to serve the freedesktop interfaces: {}", err).into(),
+                    );
+                }
+            }
+            #[cfg(not(feature = "freedesktop"))]
+            return Err("xidlehook was built without freedesktop support".into());
+        }
+
+        let mut logind_rx: Option<mpsc::Receiver<logind::Signal>> = None;
+        if let Some(ref logind) = self.config.logind {
+            #[cfg(feature = "logind")]
+            {
+                let (logind_tx, rx) = mpsc::channel(4);
+                let logind = logind.clone();
+                let events = self.events.subscribe();
+                tokio::spawn(async move {
+                    let err = logind::main_loop(logind, logind_tx, events)
+                        .await
+                        .unwrap_err();
+                    warn!("logind handling errored: {}", err);
+                });
+                logind_rx = Some(rx);
+            }
+            #[cfg(not(feature = "logind"))]
+            {
+                let _ = logind;
+                return Err("xidlehook was built without logind support".into());
+            }
+        }
+
         let mut sigint = signal(SignalKind::interrupt())?;
         let mut sigchld = signal(SignalKind::child())?;
+        let mut sighup = signal(SignalKind::hangup())?;
+
+        // Without a config file, watch for the default one appearing
+        let config_path = Config::path(&self.opt).or_else(Config::default_path);
+        let config_name = config_path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_owned());
+        #[cfg(target_os = "linux")]
+        let mut config_events = match config_path {
+            Some(ref path) if self.opt.watch_config => match watch_config(path) {
+                Ok(events) => Some(events),
+                Err(err) => {
+                    warn!("Not watching {} for changes: {}", path.display(), err);
+                    None
+                },
+            },
+            None if self.opt.watch_config => {
+                warn!("There's no config file to watch for changes");
+                None
+            },
+            _ => None,
+        };
+        #[cfg(not(target_os = "linux"))]
+        {
+            if self.opt.watch_config {
+                return Err("--watch-config is only available on Linux".into());
+            }
+        }
 
         loop {
             let socket_msg = async {
@@ -180,6 +726,46 @@ impl App {
                 }
             };
 
+            let logind_signal = async {
+                if let Some(ref mut rx) = logind_rx {
+                    rx.recv().await
+                } else {
+                    std::future::pending::<()>().await;
+                    unreachable!();
+                }
+            };
+
+            let config_changed = async {
+                #[cfg(target_os = "linux")]
+                {
+                    if let Some(ref mut events) = config_events {
+                        while let Some(event) = events.next().await {
+                            if event?.name.as_ref() == config_name.as_ref() {
+                                return Ok(());
+                            }
+                        }
+                        return Err(xidlehook_core::Error::from("config watch stopped"));
+                    }
+                }
+                std::future::pending::<xidlehook_core::Result<()>>().await
+            };
+
+            let power_timeout = self.power_timeout();
+            let power_tick = async move {
+                match power_timeout {
+                    Some(timeout) => tokio::time::sleep(timeout).await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            let changes_timeout = self.changes.next_expiry();
+            let changes_tick = async move {
+                match changes_timeout {
+                    Some(timeout) => tokio::time::sleep(timeout).await,
+                    None => std::future::pending().await,
+                }
+            };
+
             tokio::select! {
                 data = socket_msg => {
                     if let Some((msg, reply)) = data {
@@ -193,7 +779,30 @@ impl App {
                         socket_rx = None;
                     }
                 },
-                res = self.xidlehook.main_async(&self.xcb) => {
+                signal = logind_signal => {
+                    if let Some(signal) = signal {
+                        if self.handle_logind(signal)? == Progress::Stop {
+                            break;
+                        }
+                    } else {
+                        logind_rx = None;
+                    }
+                },
+                corner = self.hot_corners.recv() => match corner {
+                    Some(Some(corner)) => {
+                        if self.enter_corner(corner)? == Progress::Stop {
+                            break;
+                        }
+                    },
+                    Some(None) => self.leave_corner(),
+                    None => {
+                        warn!("Stopped watching the corners");
+                        self.hot_corners.stop();
+                    },
+                },
+                _ = power_tick => self.poll_power()?,
+                _ = changes_tick => self.expire_changes()?,
+                res = self.chains.main_async(&*self.idle) => {
                     res?;
                     break;
                 },
@@ -205,9 +814,217 @@ impl App {
                     trace!("Waiting for child process");
                     let _ = wait::waitpid(None, Some(wait::WaitPidFlag::WNOHANG));
                 },
+                _ = sighup.recv() => {
+                    info!("SIGHUP received, reloading config");
+                    self.reload()?;
+                },
+                res = config_changed => match res {
+                    Ok(()) => {
+                        info!("Config file changed, reloading");
+                        self.reload()?;
+                    },
+                    Err(err) => {
+                        warn!("Stopped watching the config file: {}", err);
+                        #[cfg(target_os = "linux")]
+                        {
+                            config_events = None;
+                        }
+                    },
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Re-read the config and replace all timers and modules. On
+    /// errors, the old config stays in place.
+    fn reload(&mut self) -> xidlehook_core::Result<()> {
+        let loaded = Config::load(&self.opt).and_then(|config| {
+            let modules = modules(&config, self.xcb.as_ref(), &self.inhibit, true)?;
+            let others = other_chains(&config, self.xcb.as_ref(), &self.inhibit, &self.events)?;
+            let hot_corners = if config.corners != self.config.corners {
+                Some(HotCorners::new(&config.corners)?)
+            } else {
+                None
+            };
+            Ok((config, modules, others, hot_corners))
+        });
+        let (config, modules, others, hot_corners) = match loaded {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                warn!("Failed to reload config, keeping the old one: {}", err);
+                return Ok(());
+            },
+        };
+        if config.socket != self.config.socket {
+            warn!("The socket can't be changed without restarting xidlehook");
+        }
+        if config.freedesktop != self.config.freedesktop {
+            warn!("D-Bus support can't be toggled without restarting xidlehook");
+        }
+        if config.logind != self.config.logind {
+            warn!("The logind options can't be changed without restarting xidlehook");
+        }
+        if config.state_path() != self.config.state_path() {
+            warn!("The state file can't be changed without restarting xidlehook");
+        }
+
+        // The corners may do something else now
+        if let Some(hot_corners) = hot_corners {
+            self.leave_corner();
+            self.hot_corners = hot_corners;
+        }
+
+        // Chains whose timers stay the same carry on with the new
+        // modules, while the others are finished off with the old timers
+        // and start over with the new ones, along with the changes made
+        // over the socket
+        self.profile = power::initial_profile(&config);
+        let main_chain = self.main_chain_mut();
+        main_chain.module_mut().1 = modules;
+        main_chain.set_detect_sleep(config.detect_sleep);
+
+        let removed: Vec<String> = self
+            .chains
+            .names()
+            .filter(|&name| name != MAIN_CHAIN && others.iter().all(|(other, _)| other != name))
+            .map(String::from)
+            .collect();
+        for name in removed {
+            if let Some(mut chain) = self.chains.remove(&name) {
+                chain.abort()?;
+            }
+        }
+        let mut added = Vec::new();
+        for (name, mut chain) in others {
+            match self.chains.get_mut(&name) {
+                Some(old) => {
+                    mem::swap(&mut old.module_mut().1, &mut chain.module_mut().1);
+                    old.set_detect_sleep(config.detect_sleep);
+                },
+                None => {
+                    self.chains.insert(name.clone(), chain);
+                    added.push(name);
+                },
             }
         }
 
+        self.config = Config {
+            socket: self.config.socket.take(),
+            freedesktop: self.config.freedesktop,
+            logind: self.config.logind.take(),
+            persist: self.config.persist,
+            state_file: self.config.state_file.take(),
+            ..config
+        };
+        let names: Vec<String> = self.chains.names().map(String::from).collect();
+        for name in names {
+            if added.contains(&name) {
+                self.rebuild(&name)?;
+            } else {
+                self.reload_timers(&name)?;
+            }
+        }
+
+        debug!(
+            "Reloaded config with {} timers in {} chains",
+            self.chains
+                .iter()
+                .map(|(_, chain)| chain.timers().len())
+                .sum::<usize>(),
+            self.chains.len()
+        );
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{cell::Cell, process};
+
+    /// A config file that's removed when dropped
+    pub struct TempConfig(PathBuf);
+    impl TempConfig {
+        pub fn new(name: &str, text: &str) -> Self {
+            let path =
+                env::temp_dir().join(format!("xidlehook-config-{}-{}.toml", process::id(), name));
+            fs::write(&path, text).unwrap();
+            TempConfig(path)
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+    impl Drop for TempConfig {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// An idle time that's set by hand
+    #[derive(Default)]
+    pub struct FakeIdle(pub Cell<Duration>);
+    impl IdleSource for FakeIdle {
+        fn get_idle(&self) -> xidlehook_core::Result<Duration> {
+            Ok(self.0.get())
+        }
+    }
+
+    /// Set up xidlehook with a config file, but without X
+    pub fn app(config: &TempConfig, idle: Rc<FakeIdle>) -> App {
+        let path = config.path().to_str().unwrap();
+        let opt = Opt::from_iter(&["xidlehook", "--config", path]);
+        let config = Config::load(&opt).unwrap();
+        App::new(opt, config, None, idle).unwrap()
+    }
+
+    const CONFIG: &str = r#"
+        [[timer]]
+        time = "1m"
+
+        [[timer]]
+        time = "10m"
+
+        [[chain]]
+        name = "music"
+
+        [[chain.timer]]
+        time = "5m"
+    "#;
+
+    #[test]
+    fn reload_keeps_unchanged_chains() {
+        let file = TempConfig::new("reload", CONFIG);
+        let idle = Rc::new(FakeIdle::default());
+        let mut app = app(&file, Rc::clone(&idle));
+
+        idle.0.set(Duration::from_secs(6 * 60));
+        for name in &[MAIN_CHAIN, "music"] {
+            let chain = app.chains.get_mut(name).unwrap();
+            chain.trigger(0, idle.0.get(), true).unwrap();
+            assert_eq!(chain.next_index(), 1);
+        }
+
+        // Nothing changed
+        app.reload().unwrap();
+        for name in &[MAIN_CHAIN, "music"] {
+            let chain = &app.chains.get(name).unwrap();
+            assert_eq!(chain.next_index(), 1, "{}", name);
+            assert!(!chain.aborted(), "{}", name);
+        }
+
+        // Only the main chain changed
+        fs::write(file.path(), CONFIG.replace("10m", "20m")).unwrap();
+        app.reload().unwrap();
+        assert_eq!(app.main_chain().next_index(), 0);
+        assert_eq!(
+            app.main_chain().timers()[1].get_time(),
+            Duration::from_secs(20 * 60)
+        );
+        assert_eq!(app.chains.get("music").unwrap().next_index(), 1);
+    }
+}
@@ -0,0 +1,57 @@
+use std::fmt;
+
+use xidlehook_core::{Error, Event, Module, Progress, Result, TimerInfo};
+
+/// Wraps a module to give it a name, and to remember whether it was
+/// the reason the chain stopped progressing
+pub struct Named {
+    name: &'static str,
+    module: Box<dyn Module>,
+    inhibiting: bool,
+}
+impl Named {
+    pub fn new<M: Module + 'static>(name: &'static str, module: M) -> Self {
+        Self {
+            name,
+            module: Box::new(module),
+            inhibiting: false,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns true if this module prevented the last timer from
+    /// activating, and the chain hasn't started over since
+    pub fn inhibiting(&self) -> bool {
+        self.inhibiting
+    }
+}
+impl Module for Named {
+    fn pre_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        let progress = self.module.pre_timer(timer)?;
+        self.inhibiting = progress == Progress::Abort || progress == Progress::Reset;
+        Ok(progress)
+    }
+    fn post_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        self.module.post_timer(timer)
+    }
+    fn warning(&mut self, error: &Error) -> Result<()> {
+        self.module.warning(error)
+    }
+    fn reset(&mut self) -> Result<()> {
+        self.module.reset()
+    }
+    fn event(&mut self, event: Event) -> Result<()> {
+        if event == Event::Active || event == Event::Reset {
+            self.inhibiting = false;
+        }
+        self.module.event(event)
+    }
+}
+impl fmt::Debug for Named {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
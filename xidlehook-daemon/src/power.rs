@@ -0,0 +1,101 @@
+//! Picks the timers by power source, using the sets in the `[power]`
+//! table: one for AC, one for battery and one for when the battery is
+//! running low. The power supplies are read from time to time, but
+//! only when any such set is configured, and the main chain starts
+//! over with the other timers whenever the power source changes.
+
+use crate::{config::Config, App, MAIN_CHAIN};
+
+use std::{fmt, time::Duration};
+
+use log::{debug, info, warn};
+
+/// How often to read the power supplies
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Which set of timers is in use
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    Ac,
+    Battery,
+    LowBattery,
+}
+impl Profile {
+    pub const ALL: [Self; 3] = [Profile::Ac, Profile::Battery, Profile::LowBattery];
+}
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Profile::Ac => write!(f, "ac"),
+            Profile::Battery => write!(f, "battery"),
+            Profile::LowBattery => write!(f, "low_battery"),
+        }
+    }
+}
+
+/// Read the power supplies to find out which profile to use, or
+/// `None` if the config has no power profiles
+#[cfg(target_os = "linux")]
+pub fn read_profile(config: &Config) -> xidlehook_core::Result<Option<Profile>> {
+    if !config.power.enabled() {
+        return Ok(None);
+    }
+    let status = xidlehook_core::modules::PowerSupply::new().status()?;
+    let profile = match status.capacity {
+        _ if status.ac => Profile::Ac,
+        Some(capacity) if capacity <= config.power.low_capacity => Profile::LowBattery,
+        _ => Profile::Battery,
+    };
+    Ok(Some(profile))
+}
+#[cfg(not(target_os = "linux"))]
+pub fn read_profile(config: &Config) -> xidlehook_core::Result<Option<Profile>> {
+    if !config.power.enabled() {
+        return Ok(None);
+    }
+    Err("power profiles are only supported on Linux".into())
+}
+
+/// Like `read_profile`, but falls back to the `[[timer]]`s if the
+/// power supplies can't be read
+pub fn initial_profile(config: &Config) -> Option<Profile> {
+    read_profile(config).unwrap_or_else(|err| {
+        warn!("Failed to read the power supplies: {}", err);
+        None
+    })
+}
+
+impl App {
+    /// Returns how long until the power supplies should be read
+    /// again, or `None` if there are no power profiles
+    pub fn power_timeout(&self) -> Option<Duration> {
+        if self.config.power.enabled() {
+            Some(POLL_INTERVAL)
+        } else {
+            None
+        }
+    }
+
+    /// Read the power supplies, and switch to the other timers if the
+    /// power source has changed
+    pub fn poll_power(&mut self) -> xidlehook_core::Result<()> {
+        let profile = match read_profile(&self.config) {
+            Ok(profile) => profile,
+            Err(err) => {
+                // Keep whichever timers are in use
+                debug!("Failed to read the power supplies: {}", err);
+                return Ok(());
+            },
+        };
+        if profile == self.profile {
+            return Ok(());
+        }
+        match profile {
+            Some(profile) => info!("Switching to the {} timers", profile),
+            None => info!("Switching to the default timers"),
+        }
+
+        self.profile = profile;
+        self.rebuild(MAIN_CHAIN)
+    }
+}
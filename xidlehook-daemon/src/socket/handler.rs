@@ -1,29 +1,89 @@
 use super::models::*;
-use crate::{timers::CmdTimer, App};
+use crate::{
+    state::{Change, Edit},
+    App, Chain, MAIN_CHAIN,
+};
 
-use std::convert::TryInto;
+use std::{
+    convert::TryInto,
+    time::{Duration, Instant},
+};
 
 use xidlehook_core::Progress;
 
+/// Reply with an error if there is no such chain
+macro_rules! chain {
+    ($self:expr, $name:expr) => {
+        match $self.chains.get_mut($name.as_deref().unwrap_or(MAIN_CHAIN)) {
+            Some(chain) => chain,
+            None => return Ok(Some(Reply::Error(String::from("no such chain")))),
+        }
+    };
+}
+
+/// Returns the state of a chain
+fn chain_status(
+    name: &str,
+    chain: &mut Chain,
+    idle: Duration,
+) -> xidlehook_core::Result<ChainStatus> {
+    let inhibitors = chain
+        .module()
+        .1
+        .iter()
+        .filter(|module| module.inhibiting())
+        .map(|module| String::from(module.name()))
+        .collect();
+    Ok(ChainStatus {
+        name: String::from(name),
+        next_index: chain
+            .next_index()
+            .try_into()
+            .expect("xidlehook does not yet handle this many timers"),
+        aborted: chain.aborted(),
+        time_left: chain.time_left(idle)?,
+        inhibitors,
+    })
+}
+
 impl App {
     pub fn handle_socket(&mut self, msg: Message) -> xidlehook_core::Result<Option<Reply>> {
         match msg {
             Message::Add(add) => {
-                let timers = self.xidlehook.timers_mut()?;
+                let timers = chain!(self, add.chain).timers_mut()?;
 
-                let index = add.index.map_or_else(|| timers.len(), usize::from);
-                if index > timers.len() {
-                    return Ok(Some(Reply::Error(String::from("index > length"))));
-                }
-                timers.insert(
+                let index = add.index.unwrap_or_else(|| {
+                    timers
+                        .len()
+                        .try_into()
+                        .expect("xidlehook does not yet handle this many timers")
+                });
+                let edit = Edit::Add {
                     index,
-                    CmdTimer::from_parts(add.time, add.activation, add.abortion, add.deactivation),
-                );
+                    time: add.time,
+                    activation: add.activation,
+                    abortion: add.abortion,
+                    deactivation: add.deactivation,
+                };
+                if let Err(err) = edit.apply(timers) {
+                    return Ok(Some(Reply::Error(err)));
+                }
+                self.changes.push(Change::new(add.chain, add.timeout, edit));
+                self.changes.save();
 
                 Ok(Some(Reply::Empty))
             },
             Message::Control(control) => {
-                let len = self.xidlehook.timers().len();
+                let trigger = matches!(control.action, Action::Trigger);
+                if trigger && control.timeout.is_some() {
+                    return Ok(Some(Reply::Error(String::from(
+                        "triggering can't be undone",
+                    ))));
+                }
+
+                let idle = self.idle.get_idle()?;
+                let chain = chain!(self, control.chain);
+                let len = chain.timers().len();
 
                 // Relies on control.timer being sorted, which it is, because it's a btreeset
                 let mut removed = 0;
@@ -31,55 +91,101 @@ impl App {
                     len.try_into()
                         .expect("xidlehook does not yet handle this many timers"),
                 ) {
-                    let timers = self.xidlehook.timers();
                     let id = match id.checked_sub(removed) {
-                        Some(res) => usize::from(res),
+                        Some(res) => res,
                         None => continue,
                     };
-                    if id >= timers.len() {
+                    if usize::from(id) >= chain.timers().len() {
                         continue;
                     }
 
-                    match control.action {
-                        Action::Disable => {
-                            let timers = self.xidlehook.timers_mut()?;
-                            timers[id].set_disabled(true);
-                        },
-                        Action::Enable => {
-                            let timers = self.xidlehook.timers_mut()?;
-                            timers[id].set_disabled(false);
-                        },
+                    let edit = match control.action {
+                        Action::Disable => Edit::Disable { timer: id },
+                        Action::Enable => Edit::Enable { timer: id },
                         Action::Trigger => {
-                            if self.xidlehook.trigger(id, self.xcb.get_idle()?, true)?
-                                == Progress::Stop
-                            {
+                            if chain.trigger(usize::from(id), idle, true)? == Progress::Stop {
                                 return Ok(None);
                             }
+                            continue;
                         },
                         Action::Delete => {
-                            let timers = self.xidlehook.timers_mut()?;
-                            // TODO: Probably want to use `retain` to optimize this...
-                            timers.remove(id);
-
-                            // Working with this large indices pointing to an allocated object... I
-                            // think we're fine
                             removed += 1;
+                            Edit::Delete { timer: id }
                         },
-                    }
+                    };
+                    edit.apply(chain.timers_mut()?)?;
+                    self.changes
+                        .push(Change::new(control.chain.clone(), control.timeout, edit));
+                }
+                if !trigger {
+                    self.changes.save();
                 }
 
                 Ok(Some(Reply::Empty))
             },
             Message::ResetIdle => {
-                self.xidlehook.reset(self.xcb.get_idle()?)?;
+                self.chains.reset(self.idle.get_idle()?)?;
                 Ok(Some(Reply::Empty))
             },
             Message::StopTimers => {
-                self.xidlehook.abort()?;
+                self.chains.abort()?;
                 Ok(Some(Reply::Empty))
             },
+            Message::Status => {
+                let idle = self.idle.get_idle()?;
+                let mut main = None;
+                let mut chains = Vec::new();
+                for (name, chain) in self.chains.iter_mut() {
+                    let status = chain_status(name, chain, idle)?;
+                    if name == MAIN_CHAIN {
+                        main = Some(status);
+                    } else {
+                        chains.push(status);
+                    }
+                }
+                let main = main.expect("the main chain is never removed");
+                Ok(Some(Reply::Status(Status {
+                    idle,
+                    next_index: main.next_index,
+                    aborted: main.aborted,
+                    time_left: main.time_left,
+                    inhibitors: main.inhibitors,
+                    inhibitions: self
+                        .inhibit
+                        .inhibitors()
+                        .into_iter()
+                        .map(|inhibitor| Inhibition {
+                            cookie: inhibitor.cookie,
+                            reason: inhibitor.reason,
+                            owner: inhibitor.owner,
+                            expires_in: inhibitor
+                                .expires
+                                .map(|expires| expires.saturating_duration_since(Instant::now())),
+                        })
+                        .collect(),
+                    detect_sleep: self.main_chain().detect_sleep(),
+                    chains,
+                })))
+            },
+            Message::Inhibit(inhibit) => {
+                let cookie = self
+                    .inhibit
+                    .inhibit(inhibit.reason, inhibit.owner, inhibit.timeout);
+                Ok(Some(Reply::Cookie(cookie)))
+            },
+            Message::Uninhibit(uninhibit) => {
+                if self.inhibit.uninhibit(uninhibit.cookie) {
+                    Ok(Some(Reply::Empty))
+                } else {
+                    Ok(Some(Reply::Error(String::from("no such inhibitor"))))
+                }
+            },
+            // Subscriptions never leave the socket task
+            Message::Subscribe => Ok(Some(Reply::Error(String::from(
+                "subscriptions are handled by the socket",
+            )))),
             Message::Query(query) => {
-                let timers = self.xidlehook.timers();
+                let timers = chain!(self, query.chain).timers();
                 let mut output = Vec::new();
 
                 for id in query.timer.iter(
@@ -107,3 +213,93 @@ impl App {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::rc::Rc;
+
+    use crate::tests::{app, FakeIdle, TempConfig};
+
+    const CONFIG: &str = r#"
+        [[timer]]
+        time = "1m"
+
+        [[timer]]
+        time = "10m"
+
+        [[chain]]
+        name = "music"
+
+        [[chain.timer]]
+        time = "5m"
+
+        [[chain]]
+        name = "backup"
+
+        [[chain.timer]]
+        time = "1m"
+    "#;
+
+    #[test]
+    fn status() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let file = TempConfig::new("status", CONFIG);
+        let idle = Rc::new(FakeIdle::default());
+        let mut app = app(&file, Rc::clone(&idle));
+
+        // The main chain is running its first timer, one of the others
+        // was aborted after running it, and the last one was kept from
+        // running it by an inhibitor
+        idle.0.set(Duration::from_secs(2 * 60));
+        app.main_chain_mut().trigger(0, idle.0.get(), true).unwrap();
+        let backup = app.chains.get_mut("backup").unwrap();
+        backup.trigger(0, idle.0.get(), true).unwrap();
+        backup.abort().unwrap();
+        let cookie = app
+            .inhibit
+            .inhibit(String::from("call"), String::from("test"), None);
+        let music = app.chains.get_mut("music").unwrap();
+        assert_eq!(
+            music.trigger(0, idle.0.get(), false).unwrap(),
+            Progress::Reset
+        );
+        idle.0.set(Duration::from_secs(3 * 60));
+
+        let status = match app.handle_socket(Message::Status).unwrap() {
+            Some(Reply::Status(status)) => status,
+            reply => panic!("unexpected reply {:?}", reply),
+        };
+        assert_eq!(status.idle, Duration::from_secs(3 * 60));
+        assert_eq!(status.next_index, 1);
+        assert!(!status.aborted);
+        assert_eq!(status.time_left, Some(Duration::from_secs(9 * 60)));
+        assert!(status.inhibitors.is_empty());
+        assert_eq!(status.inhibitions.len(), 1);
+        assert_eq!(status.inhibitions[0].cookie, cookie);
+        assert_eq!(status.inhibitions[0].reason, "call");
+        assert_eq!(status.inhibitions[0].owner, "test");
+        assert_eq!(status.inhibitions[0].expires_in, None);
+
+        let chain = |name| {
+            status
+                .chains
+                .iter()
+                .find(|chain| chain.name == name)
+                .unwrap()
+        };
+        assert_eq!(status.chains.len(), 2);
+        let backup = chain("backup");
+        assert_eq!(backup.next_index, 1);
+        assert!(backup.aborted);
+        assert_eq!(backup.time_left, None);
+        assert!(backup.inhibitors.is_empty());
+        let music = chain("music");
+        assert_eq!(music.next_index, 0);
+        assert!(!music.aborted);
+        assert_eq!(music.time_left, Some(Duration::from_secs(4 * 60)));
+        assert_eq!(music.inhibitors, vec![String::from("inhibit")]);
+    }
+}
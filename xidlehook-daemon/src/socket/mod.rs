@@ -1,20 +1,91 @@
-use std::{convert::Infallible, fs};
+use std::{
+    convert::{Infallible, TryInto},
+    fs,
+};
 
 use log::{trace, warn};
 use tokio::{
-    io::{BufReader, BufWriter, AsyncWriteExt, AsyncBufReadExt},
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter, Lines},
     net::UnixListener,
-    sync::{mpsc, oneshot},
+    sync::{broadcast, mpsc, oneshot},
 };
+use xidlehook_core::Module;
 
 pub mod handler;
 pub mod models;
 
 pub use self::models::*;
 
+/// A module which forwards all events of a chain to subscribers of the
+/// socket
+pub struct Broadcast {
+    pub events: broadcast::Sender<ChainEvent>,
+    /// The name of the chain, or `None` for the main one
+    pub chain: Option<String>,
+}
+impl Module for Broadcast {
+    fn event(&mut self, event: xidlehook_core::Event) -> xidlehook_core::Result<()> {
+        let id = |info: xidlehook_core::TimerInfo| {
+            info.index
+                .try_into()
+                .expect("xidlehook does not yet handle this many timers")
+        };
+        let event = match event {
+            xidlehook_core::Event::Idle => Event::Idle,
+            xidlehook_core::Event::Active => Event::Active,
+            xidlehook_core::Event::Activated(info) => Event::Activated { timer: id(info) },
+            xidlehook_core::Event::Deactivated(info) => Event::Deactivated { timer: id(info) },
+            xidlehook_core::Event::Aborted(info) => Event::Aborted { timer: id(info) },
+            xidlehook_core::Event::Reset => Event::Reset,
+        };
+        // Fails if nobody is subscribed, which is fine
+        let _ = self.events.send(ChainEvent {
+            chain: self.chain.clone(),
+            event,
+        });
+        Ok(())
+    }
+}
+
+/// Send all events to a subscriber, until it disconnects. Anything it
+/// sends is ignored, but reading is how a subscriber closing the
+/// connection is noticed before the next event.
+async fn subscribe<R, W>(
+    lines: &mut Lines<R>,
+    writer: &mut W,
+    mut events: broadcast::Receiver<ChainEvent>,
+) -> std::io::Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            line = lines.next_line() => match line? {
+                Some(_) => continue,
+                None => return Ok(()),
+            },
+        };
+        let event = match event {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("subscriber is too slow, skipped {} events", skipped);
+                continue;
+            },
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+        let msg = serde_json::to_vec(&event)?;
+        writer.write_all(&msg).await?;
+        writer.write_all(&[b'\n']).await?;
+        writer.flush().await?;
+    }
+}
+
 pub async fn main_loop(
     address: &str,
     socket_tx: mpsc::Sender<(Message, oneshot::Sender<Reply>)>,
+    events: broadcast::Sender<ChainEvent>,
 ) -> xidlehook_core::Result<Infallible> {
     let _ = fs::remove_file(address);
 
@@ -26,6 +97,7 @@ pub async fn main_loop(
         trace!("Connection from {:?}", addr);
 
         let socket_tx = socket_tx.clone();
+        let events = events.clone();
         tokio::spawn(async move {
             let (reader, writer) = stream.split();
             let reader = BufReader::new(reader);
@@ -42,6 +114,15 @@ pub async fn main_loop(
                     },
                 };
 
+                if let Message::Subscribe = msg {
+                    trace!("Subscribing {:?} to events", addr);
+                    let events = events.subscribe();
+                    if let Err(err) = subscribe(&mut lines, &mut writer, events).await {
+                        trace!("Subscriber went away: {}", err);
+                    }
+                    break;
+                }
+
                 let (reply_tx, reply_rx) = oneshot::channel();
                 socket_tx
                     .send((msg, reply_tx))
@@ -68,3 +149,95 @@ pub async fn main_loop(
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, process, time::Duration};
+
+    use tokio::{net::UnixStream, time};
+    use xidlehook_core::Xidlehook;
+
+    use crate::timers::CmdTimer;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Wait until `done` returns true, or panic after `TIMEOUT`
+    async fn eventually(mut done: impl FnMut() -> bool) {
+        time::timeout(TIMEOUT, async {
+            while !done() {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("timed out");
+    }
+
+    #[tokio::test]
+    async fn subscribe() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let address = env::temp_dir()
+            .join(format!("xidlehook-socket-{}.sock", process::id()))
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let (socket_tx, _socket_rx) = mpsc::channel(1);
+        let (events, _) = broadcast::channel(16);
+        let server = tokio::spawn({
+            let address = address.clone();
+            let events = events.clone();
+            async move {
+                let _ = main_loop(&address, socket_tx, events).await;
+            }
+        });
+
+        let mut client = None;
+        eventually(|| {
+            client = std::os::unix::net::UnixStream::connect(&address).ok();
+            client.is_some()
+        })
+        .await;
+        let client = client.unwrap();
+        client.set_nonblocking(true).unwrap();
+        let mut client = UnixStream::from_std(client).unwrap();
+        client
+            .write_all(b"{\"type\":\"subscribe\"}\n")
+            .await
+            .unwrap();
+        eventually(|| events.receiver_count() == 1).await;
+
+        let mut chain = Xidlehook::new(vec![CmdTimer::from_parts(
+            Duration::from_secs(60),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        )])
+        .register(Broadcast {
+            events: events.clone(),
+            chain: Some(String::from("music")),
+        });
+        chain.trigger(0, Duration::from_secs(60), true).unwrap();
+
+        let mut lines = BufReader::new(&mut client).lines();
+        for &expected in &[
+            r#"{"chain":"music","type":"idle"}"#,
+            r#"{"chain":"music","type":"activated","timer":0}"#,
+        ] {
+            let line = time::timeout(TIMEOUT, lines.next_line())
+                .await
+                .expect("timed out")
+                .unwrap();
+            assert_eq!(line.as_deref(), Some(expected));
+        }
+
+        // Hanging up is noticed without waiting for another event
+        drop(lines);
+        drop(client);
+        eventually(|| events.receiver_count() == 0).await;
+
+        server.abort();
+        let _ = fs::remove_file(&address);
+    }
+}
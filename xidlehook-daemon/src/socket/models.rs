@@ -3,6 +3,62 @@ use std::{collections::BTreeSet, time::Duration};
 use serde::{Deserialize, Serialize};
 
 pub type TimerId = u16;
+pub type Cookie = xidlehook_core::modules::inhibit::Cookie;
+
+/// (De)serialize durations in the human-friendly format (see
+/// `xidlehook_core::duration`), while still accepting a number of
+/// seconds or serde's default `{secs, nanos}` representation
+pub mod human_duration {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Human(String),
+        Secs(u64),
+        Legacy(Duration),
+    }
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&xidlehook_core::duration::format(*duration))
+    }
+
+    /// The same, but for optional durations
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::time::Duration;
+
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super")] Duration);
+
+        pub fn serialize<S: Serializer>(
+            duration: &Option<Duration>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match *duration {
+                Some(duration) => super::serialize(&duration, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Duration>, D::Error> {
+            Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|wrapper| wrapper.0))
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        match Repr::deserialize(deserializer)? {
+            Repr::Human(input) => {
+                xidlehook_core::duration::parse(&input).map_err(de::Error::custom)
+            },
+            Repr::Secs(secs) => Ok(Duration::from_secs(secs)),
+            Repr::Legacy(duration) => Ok(duration),
+        }
+    }
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
@@ -37,26 +93,58 @@ pub enum Action {
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Add {
+    /// The chain to add the timer to, defaults to the main one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<String>,
     pub index: Option<TimerId>,
+    #[serde(with = "human_duration")]
     pub time: Duration,
     pub activation: Vec<String>,
     pub abortion: Vec<String>,
     pub deactivation: Vec<String>,
+    /// Remove the timer again after this long
+    #[serde(default, with = "human_duration::option")]
+    pub timeout: Option<Duration>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Control {
+    /// The chain of the timers, defaults to the main one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<String>,
     #[serde(default)]
     pub timer: Filter,
     pub action: Action,
+    /// Undo the action after this long. Triggering can't be undone.
+    #[serde(default, with = "human_duration::option")]
+    pub timeout: Option<Duration>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Query {
+    /// The chain of the timers, defaults to the main one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<String>,
     #[serde(default)]
     pub timer: Filter,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inhibit {
+    /// Why the chain is inhibited, such as "Watching a movie"
+    pub reason: String,
+    /// Who is inhibiting the chain, such as an application name
+    pub owner: String,
+    /// Release the inhibitor automatically after this long
+    #[serde(default, with = "human_duration::option")]
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Uninhibit {
+    pub cookie: Cookie,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum Message {
@@ -65,6 +153,13 @@ pub enum Message {
     ResetIdle,
     StopTimers,
     Query(Query),
+    /// Keep the connection open and receive an `Event` per line
+    Subscribe,
+    Status,
+    /// Keep the chain from progressing until uninhibited, replies with
+    /// a cookie
+    Inhibit(Inhibit),
+    Uninhibit(Uninhibit),
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -76,10 +171,78 @@ pub struct QueryResult {
     pub deactivation: Vec<String>,
     pub disabled: bool,
 }
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Status {
+    /// How long the user has been idle
+    #[serde(with = "human_duration")]
+    pub idle: Duration,
+    /// The index of the next timer to activate, which equals the
+    /// amount of timers once all have been activated
+    pub next_index: TimerId,
+    /// Whether the chain was aborted, meaning no more timers activate
+    /// until the user becomes active
+    pub aborted: bool,
+    /// How long until the next timer activates, if ever
+    #[serde(with = "human_duration::option")]
+    pub time_left: Option<Duration>,
+    /// The modules that stopped the chain from progressing
+    pub inhibitors: Vec<String>,
+    /// Everyone who inhibited the chain (see `Message::Inhibit`)
+    pub inhibitions: Vec<Inhibition>,
+    pub detect_sleep: bool,
+    /// The chains other than the main one
+    pub chains: Vec<ChainStatus>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ChainStatus {
+    pub name: String,
+    /// See `Status::next_index`
+    pub next_index: TimerId,
+    pub aborted: bool,
+    #[serde(with = "human_duration::option")]
+    pub time_left: Option<Duration>,
+    pub inhibitors: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inhibition {
+    pub cookie: Cookie,
+    pub reason: String,
+    pub owner: String,
+    /// How long until this inhibitor is released automatically, if
+    /// ever
+    #[serde(with = "human_duration::option")]
+    pub expires_in: Option<Duration>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Event {
+    Idle,
+    Active,
+    Activated { timer: TimerId },
+    Deactivated { timer: TimerId },
+    Aborted { timer: TimerId },
+    Reset,
+}
+
+/// An event along with the chain it happened in
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChainEvent {
+    /// The name of the chain, or `None` for the main one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<String>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum Reply {
     Empty,
     Error(String),
     QueryResult(Vec<QueryResult>),
+    Status(Status),
+    Cookie(Cookie),
 }
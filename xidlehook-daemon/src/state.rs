@@ -0,0 +1,597 @@
+//! Changes made to the timers over the socket, such as adding a timer
+//! or disabling one for the rest of the day. They're kept as a list
+//! that's replayed on top of the configured timers whenever those are
+//! rebuilt, and dropped once they expire. With a state file, the list
+//! is also saved so that it survives restarting xidlehook.
+//!
+//! Changes refer to timers by their index at the time, so once a
+//! change that adds or deletes a timer is dropped, the later changes
+//! to the same chain are dropped along with it.
+
+use crate::{
+    config::TimerConfig,
+    socket::models::{human_duration, TimerId},
+    timers::CmdTimer,
+    App, MAIN_CHAIN,
+};
+
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+/// What a change does to the timers of a chain
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Edit {
+    Add {
+        index: TimerId,
+        #[serde(with = "human_duration")]
+        time: Duration,
+        activation: Vec<String>,
+        abortion: Vec<String>,
+        deactivation: Vec<String>,
+    },
+    Disable {
+        timer: TimerId,
+    },
+    Enable {
+        timer: TimerId,
+    },
+    Delete {
+        timer: TimerId,
+    },
+}
+impl Edit {
+    /// Make this change to a list of timers
+    pub fn apply(&self, timers: &mut Vec<CmdTimer>) -> Result<(), String> {
+        let len = timers.len();
+        let check = |id: TimerId| {
+            let id = usize::from(id);
+            if id < len {
+                Ok(id)
+            } else {
+                Err(format!("timer {} doesn't exist", id))
+            }
+        };
+        match *self {
+            Edit::Add {
+                index,
+                time,
+                ref activation,
+                ref abortion,
+                ref deactivation,
+            } => {
+                if usize::from(index) > len {
+                    return Err(String::from("index > length"));
+                }
+                timers.insert(
+                    usize::from(index),
+                    CmdTimer::from_parts(
+                        time,
+                        activation.clone(),
+                        abortion.clone(),
+                        deactivation.clone(),
+                    ),
+                );
+            },
+            Edit::Disable { timer } => timers[check(timer)?].set_disabled(true),
+            Edit::Enable { timer } => timers[check(timer)?].set_disabled(false),
+            Edit::Delete { timer } => {
+                timers.remove(check(timer)?);
+            },
+        }
+        Ok(())
+    }
+
+    /// Returns true if this moves the timers after it to another index
+    fn moves_timers(&self) -> bool {
+        matches!(self, Edit::Add { .. } | Edit::Delete { .. })
+    }
+}
+
+/// A change to the timers, along with which chain it applies to and
+/// when it's undone
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Change {
+    /// The name of the chain, or `None` for the main one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<String>,
+    /// When the change is undone, in seconds since the Unix epoch, so
+    /// that it means the same after a restart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+    #[serde(flatten)]
+    pub edit: Edit,
+}
+impl Change {
+    /// Create a change that's undone after `timeout`, if any
+    pub fn new(chain: Option<String>, timeout: Option<Duration>, edit: Edit) -> Self {
+        Self {
+            chain: chain.filter(|name| name != MAIN_CHAIN),
+            expires: timeout.map(|timeout| now().saturating_add(round_up(timeout))),
+            edit,
+        }
+    }
+
+    /// Returns the name of the chain this change applies to
+    pub fn chain(&self) -> &str {
+        self.chain.as_deref().unwrap_or(MAIN_CHAIN)
+    }
+}
+
+/// The contents of the state file
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+struct StateFile {
+    changes: Vec<Change>,
+}
+
+/// See the module-level documentation
+#[derive(Debug, Default)]
+pub struct Changes {
+    path: Option<PathBuf>,
+    changes: Vec<Change>,
+}
+impl Changes {
+    /// Read the changes from a state file, if any. A state file that
+    /// can't be read is started over.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let mut changes = Vec::new();
+        if let Some(ref path) = path {
+            match fs::read_to_string(path) {
+                Ok(text) => match serde_json::from_str::<StateFile>(&text) {
+                    Ok(state) => changes = state.changes,
+                    Err(err) => warn!("Failed to parse {}: {}", path.display(), err),
+                },
+                Err(err) => debug!("Not restoring any changes from {}: {}", path.display(), err),
+            }
+        }
+        let mut changes = Self { path, changes };
+        changes.expire();
+        changes
+    }
+
+    /// Remember a change. It's only written to the state file by
+    /// `save`.
+    pub fn push(&mut self, change: Change) {
+        self.changes.push(change);
+    }
+
+    /// Write the changes to the state file, if any
+    pub fn save(&self) {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return,
+        };
+        let res = (|| -> xidlehook_core::Result<()> {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let state = StateFile {
+                changes: self.changes.clone(),
+            };
+            // Replace the file at once, so a crash can't leave half
+            // of it behind
+            let tmp = path.with_extension("tmp");
+            fs::write(&tmp, serde_json::to_vec_pretty(&state)?)?;
+            fs::rename(&tmp, path)?;
+            Ok(())
+        })();
+        if let Err(err) = res {
+            warn!("Failed to save the state to {}: {}", path.display(), err);
+        }
+    }
+
+    /// Returns true if any change applies to this chain
+    pub fn touches(&self, chain: &str) -> bool {
+        self.changes.iter().any(|change| change.chain() == chain)
+    }
+
+    /// Make all changes of a chain to its timers. Changes that no
+    /// longer fit, such as after the config changed, are dropped.
+    pub fn apply(&mut self, chain: &str, timers: &mut Vec<CmdTimer>) {
+        let len = self.changes.len();
+        let mut moved = false;
+        self.changes.retain(|change| {
+            if change.chain() != chain {
+                return true;
+            }
+            if moved {
+                warn!(
+                    "Dropping {:?} in the {} chain, it came after one that no longer fits",
+                    change.edit, chain
+                );
+                return false;
+            }
+            match change.edit.apply(timers) {
+                Ok(()) => true,
+                Err(err) => {
+                    warn!("Dropping {:?} in the {} chain: {}", change.edit, chain, err);
+                    moved = change.edit.moves_timers();
+                    false
+                },
+            }
+        });
+        if self.changes.len() != len {
+            self.save();
+        }
+    }
+
+    /// Returns how long until the next change is undone, if ever
+    pub fn next_expiry(&self) -> Option<Duration> {
+        let now = now();
+        self.changes
+            .iter()
+            .filter_map(|change| change.expires)
+            .min()
+            .map(|expires| Duration::from_secs(expires.saturating_sub(now)))
+    }
+
+    /// Drop the changes that have expired, along with the ones that
+    /// depend on them, and return the names of the chains they applied
+    /// to
+    pub fn expire(&mut self) -> Vec<String> {
+        let now = now();
+        let mut chains: Vec<String> = Vec::new();
+        // The chains whose timers were moved by a dropped change
+        let mut moved: Vec<String> = Vec::new();
+        self.changes.retain(|change| {
+            let chain = change.chain();
+            let expired = match change.expires {
+                Some(expires) => expires <= now,
+                None => false,
+            };
+            let depends = moved.iter().any(|other| other == chain);
+            if !expired && !depends {
+                return true;
+            }
+            if depends {
+                debug!("Dropping {:?}, it came after one that expired", change.edit);
+            } else if change.edit.moves_timers() {
+                moved.push(String::from(chain));
+            }
+            if !chains.iter().any(|other| other == chain) {
+                chains.push(String::from(chain));
+            }
+            false
+        });
+        if !chains.is_empty() {
+            self.save();
+        }
+        chains
+    }
+}
+
+/// Returns the number of seconds since the Unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Returns the number of seconds in a duration, rounding up
+fn round_up(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+impl App {
+    /// Returns the configured timers of a chain
+    fn configured_timers(&self, chain: &str) -> &[TimerConfig] {
+        if chain == MAIN_CHAIN {
+            return self.config.timers(self.profile);
+        }
+        self.config
+            .chains
+            .iter()
+            .find(|other| other.name == chain)
+            .map_or(&[][..], |other| &other.timers)
+    }
+
+    /// Returns the configured timers of a chain with the changes made
+    /// on top of them
+    fn build_timers(&mut self, chain: &str) -> Vec<CmdTimer> {
+        let mut timers = self
+            .configured_timers(chain)
+            .iter()
+            .map(TimerConfig::to_timer)
+            .collect();
+        self.changes.apply(chain, &mut timers);
+        timers
+    }
+
+    /// Replace the timers of a chain with the configured ones and the
+    /// changes made on top of them, and start it over
+    pub fn rebuild(&mut self, chain: &str) -> xidlehook_core::Result<()> {
+        let timers = self.build_timers(chain);
+        let idle = self.idle.get_idle()?;
+        if let Some(chain) = self.chains.get_mut(chain) {
+            chain.abort()?;
+            *chain.timers_mut()? = timers;
+            chain.reset(idle)?;
+        }
+        Ok(())
+    }
+
+    /// Replace the timers of a chain after the config was reloaded.
+    /// Unlike `rebuild`, a chain whose timers stay the same carries on
+    /// where it was.
+    pub fn reload_timers(&mut self, name: &str) -> xidlehook_core::Result<()> {
+        let timers = self.build_timers(name);
+        let chain = match self.chains.get(name) {
+            Some(chain) => chain,
+            None => return Ok(()),
+        };
+        let unchanged = chain.timers().len() == timers.len()
+            && chain
+                .timers()
+                .iter()
+                .zip(&timers)
+                .all(|(old, new)| old.same_as(new));
+        if unchanged {
+            debug!("The timers of the {} chain stay the same", name);
+            return Ok(());
+        }
+        let idle = self.idle.get_idle()?;
+        if let Some(chain) = self.chains.get_mut(name) {
+            chain.abort()?;
+            *chain.timers_mut()? = timers;
+            chain.reset(idle)?;
+        }
+        Ok(())
+    }
+
+    /// Replace the timers of a chain after some changes were undone.
+    /// Unlike `rebuild`, the idle time counted so far still counts,
+    /// unless the timers that already activated are different now.
+    fn update(&mut self, name: &str) -> xidlehook_core::Result<()> {
+        let timers = self.build_timers(name);
+        let idle = self.idle.get_idle()?;
+        let chain = match self.chains.get_mut(name) {
+            Some(chain) => chain,
+            None => return Ok(()),
+        };
+        if chain.aborted() {
+            // Starts over with the new timers once the user is active
+            *chain.timers_mut()? = timers;
+            return Ok(());
+        }
+
+        let activated = &chain.timers()[..chain.next_index()];
+        let kept = timers.len() >= activated.len()
+            && activated
+                .iter()
+                .zip(&timers)
+                .all(|(old, new)| old.same_as(new));
+        if kept {
+            chain.replace_next_timers(timers);
+        } else {
+            debug!(
+                "The running timer of the {} chain changed, starting it over",
+                name
+            );
+            chain.abort()?;
+            *chain.timers_mut()? = timers;
+            chain.reset(idle)?;
+        }
+        Ok(())
+    }
+
+    /// Bring back the changes that were restored from the state file
+    pub fn restore_changes(&mut self) -> xidlehook_core::Result<()> {
+        let names: Vec<String> = self.chains.names().map(String::from).collect();
+        for name in names {
+            if self.changes.touches(&name) {
+                info!("Restoring the changes to the {} chain", name);
+                self.rebuild(&name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Undo the changes that have expired
+    pub fn expire_changes(&mut self) -> xidlehook_core::Result<()> {
+        for name in self.changes.expire() {
+            info!("Changes to the {} chain expired", name);
+            self.update(&name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, process};
+
+    /// A state file that's removed when dropped
+    struct TempState(PathBuf);
+    impl TempState {
+        fn new(name: &str) -> Self {
+            let path =
+                env::temp_dir().join(format!("xidlehook-state-{}-{}.json", process::id(), name));
+            let _ = fs::remove_file(&path);
+            TempState(path)
+        }
+    }
+    impl Drop for TempState {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn timer(minutes: u64) -> CmdTimer {
+        CmdTimer::from_parts(
+            Duration::from_secs(minutes * 60),
+            vec![String::from("true")],
+            Vec::new(),
+            Vec::new(),
+        )
+    }
+
+    fn add(index: TimerId, minutes: u64) -> Edit {
+        Edit::Add {
+            index,
+            time: Duration::from_secs(minutes * 60),
+            activation: vec![String::from("true")],
+            abortion: Vec::new(),
+            deactivation: Vec::new(),
+        }
+    }
+
+    fn change(chain: &str, expires: Option<u64>, edit: Edit) -> Change {
+        Change {
+            chain: Some(String::from(chain)).filter(|name| name != MAIN_CHAIN),
+            expires,
+            edit,
+        }
+    }
+
+    fn to_json(changes: &Changes) -> serde_json::Value {
+        serde_json::to_value(&changes.changes).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let state = TempState::new("round-trip");
+        let mut changes = Changes::load(Some(state.0.clone()));
+        assert!(changes.changes.is_empty());
+
+        changes.push(Change::new(None, None, add(1, 5)));
+        changes.push(Change::new(
+            Some(String::from("other")),
+            Some(Duration::from_millis(1500)),
+            Edit::Disable { timer: 0 },
+        ));
+        changes.save();
+
+        let text = fs::read_to_string(&state.0).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(json["changes"][0]["type"], "add");
+        assert_eq!(json["changes"][0]["time"], "5m");
+        assert!(json["changes"][0].get("chain").is_none());
+        assert_eq!(json["changes"][1]["type"], "disable");
+        assert_eq!(json["changes"][1]["chain"], "other");
+        // Rounded up to whole seconds
+        assert!(json["changes"][1]["expires"].as_u64().unwrap() >= now() + 2);
+
+        let restored = Changes::load(Some(state.0.clone()));
+        assert_eq!(to_json(&restored), to_json(&changes));
+
+        // A broken state file is started over
+        fs::write(&state.0, "{ not json").unwrap();
+        assert!(Changes::load(Some(state.0.clone())).changes.is_empty());
+    }
+
+    #[test]
+    fn bounds() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let mut timers = vec![timer(1), timer(2)];
+        assert_eq!(
+            Edit::Disable { timer: 2 }.apply(&mut timers),
+            Err(String::from("timer 2 doesn't exist"))
+        );
+        assert_eq!(
+            Edit::Delete { timer: 2 }.apply(&mut timers),
+            Err(String::from("timer 2 doesn't exist"))
+        );
+        assert_eq!(
+            add(3, 5).apply(&mut timers),
+            Err(String::from("index > length"))
+        );
+        assert_eq!(timers.len(), 2);
+
+        assert_eq!(add(2, 5).apply(&mut timers), Ok(()));
+        assert_eq!(Edit::Disable { timer: 2 }.apply(&mut timers), Ok(()));
+        assert!(timers[2].get_disabled());
+        assert_eq!(Edit::Delete { timer: 0 }.apply(&mut timers), Ok(()));
+        let times: Vec<u64> = timers.iter().map(|t| t.get_time().as_secs()).collect();
+        assert_eq!(times, [120, 300]);
+    }
+
+    #[test]
+    fn expiry() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let now = now();
+        let mut changes = Changes {
+            path: None,
+            changes: vec![
+                change(MAIN_CHAIN, Some(now - 1), add(0, 5)),
+                // Refers to the timers after the one above was added
+                change(MAIN_CHAIN, None, Edit::Disable { timer: 1 }),
+                change("other", Some(now + 100), Edit::Disable { timer: 0 }),
+                change("other", Some(now - 1), Edit::Enable { timer: 1 }),
+                // Disabling doesn't move the timers, so this still fits
+                change("other", None, Edit::Delete { timer: 0 }),
+            ],
+        };
+        assert_eq!(changes.next_expiry(), Some(Duration::default()));
+
+        assert_eq!(changes.expire(), [MAIN_CHAIN, "other"]);
+        assert_eq!(
+            to_json(&changes),
+            serde_json::json!([
+                { "chain": "other", "expires": now + 100, "type": "disable", "timer": 0 },
+                { "chain": "other", "type": "delete", "timer": 0 },
+            ])
+        );
+        let next = changes.next_expiry().unwrap();
+        assert!(next <= Duration::from_secs(100) && next >= Duration::from_secs(99));
+
+        assert!(changes.expire().is_empty());
+        changes.changes.clear();
+        assert_eq!(changes.next_expiry(), None);
+    }
+
+    #[test]
+    fn restore() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let state = TempState::new("restore");
+        let mut changes = Changes::load(Some(state.0.clone()));
+        changes.push(Change::new(None, None, add(1, 2)));
+        changes.push(Change::new(None, None, Edit::Disable { timer: 0 }));
+        // No longer fits, such as after removing timers from the config
+        changes.push(Change::new(None, None, Edit::Delete { timer: 5 }));
+        changes.push(Change::new(None, None, Edit::Enable { timer: 0 }));
+        changes.push(Change::new(
+            Some(String::from("other")),
+            None,
+            Edit::Delete { timer: 0 },
+        ));
+        changes.save();
+
+        let config: Vec<TimerConfig> = vec![
+            toml::from_str("time = \"1m\"\nactivation = \"xrandr --brightness 0.5\"").unwrap(),
+            toml::from_str("time = \"5m\"\nactivation = [\"i3lock\"]").unwrap(),
+        ];
+        let mut timers: Vec<CmdTimer> = config.iter().map(TimerConfig::to_timer).collect();
+
+        let mut changes = Changes::load(Some(state.0.clone()));
+        assert!(changes.touches(MAIN_CHAIN));
+        changes.apply(MAIN_CHAIN, &mut timers);
+
+        let times: Vec<u64> = timers.iter().map(|t| t.get_time().as_secs()).collect();
+        assert_eq!(times, [60, 120, 300]);
+        assert!(timers[0].get_disabled());
+        assert_eq!(timers[0].activation()[0], "/bin/sh");
+        assert_eq!(timers[1].activation(), ["true"]);
+        assert_eq!(timers[2].activation(), ["i3lock"]);
+
+        // The changes that no longer fit are gone for good, the other
+        // chain's aren't touched
+        let restored = Changes::load(Some(state.0.clone()));
+        assert_eq!(restored.changes.len(), 3);
+        assert_eq!(to_json(&restored), to_json(&changes));
+        assert!(restored.touches("other"));
+    }
+}
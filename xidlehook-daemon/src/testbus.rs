@@ -0,0 +1,94 @@
+//! A private D-Bus daemon for tests, so that they neither depend on
+//! nor disturb the session and system buses
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    process::{self, Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use dbus::{channel::Channel, nonblock::SyncConnection};
+use dbus_tokio::connection::{self, IOResource};
+
+const CONFIG: &str = r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:path=SOCKET</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#;
+
+pub struct TestBus {
+    daemon: Child,
+    dir: PathBuf,
+    pub address: String,
+}
+impl TestBus {
+    /// Start a bus, or return `None` if dbus-daemon isn't installed
+    pub fn start() -> Option<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = env::temp_dir().join(format!(
+            "xidlehook-dbus-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("bus.conf");
+        let socket = dir.join("bus");
+        fs::write(
+            &config,
+            CONFIG.replace("SOCKET", socket.to_str().expect("non-UTF-8 temp dir")),
+        )
+        .unwrap();
+
+        let mut daemon = match Command::new("dbus-daemon")
+            .arg("--nofork")
+            .arg("--print-address")
+            .arg(format!("--config-file={}", config.display()))
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(daemon) => daemon,
+            Err(err) => {
+                eprintln!("Can't run dbus-daemon: {}", err);
+                let _ = fs::remove_dir_all(&dir);
+                return None;
+            },
+        };
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        Some(Self {
+            daemon,
+            dir,
+            address: address.trim().to_owned(),
+        })
+    }
+
+    /// Connect to the bus
+    pub fn connect(&self) -> (IOResource<SyncConnection>, Arc<SyncConnection>) {
+        let mut channel = Channel::open_private(&self.address).unwrap();
+        channel.register().unwrap();
+        connection::from_channel(channel).unwrap()
+    }
+}
+impl Drop for TestBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
@@ -1,9 +1,10 @@
-use std::{process::Command, time::Duration};
+use std::{os::unix::io::RawFd, process::Command, time::Duration};
 
-use xidlehook_core::{timers::CmdTimer as Inner, Result, Timer};
+use xidlehook_core::{schedule::Schedule, timers::CmdTimer as Inner, Result, Timer};
 
 pub struct CmdTimer {
     inner: Inner,
+    schedule: Schedule,
 
     activation: Option<Vec<String>>,
     abortion: Option<Vec<String>>,
@@ -21,6 +22,7 @@ impl CmdTimer {
                 time,
                 ..Inner::default()
             },
+            schedule: Schedule::new(),
             activation: Some(activation).filter(|v| !v.is_empty()),
             abortion: Some(abortion).filter(|v| !v.is_empty()),
             deactivation: Some(deactivation).filter(|v| !v.is_empty()),
@@ -29,31 +31,6 @@ impl CmdTimer {
         me
     }
 
-    pub fn from_shell(
-        time: Duration,
-        activation: String,
-        abortion: String,
-        deactivation: String,
-    ) -> Self {
-        let mut me = Self {
-            inner: Inner {
-                time,
-                ..Inner::default()
-            },
-            activation: Some(activation)
-                .filter(|s| !s.is_empty())
-                .map(|s| vec!["/bin/sh".into(), "-c".into(), s]),
-            abortion: Some(abortion)
-                .filter(|s| !s.is_empty())
-                .map(|s| vec!["/bin/sh".into(), "-c".into(), s]),
-            deactivation: Some(deactivation)
-                .filter(|s| !s.is_empty())
-                .map(|s| vec!["/bin/sh".into(), "-c".into(), s]),
-        };
-        me.sync();
-        me
-    }
-
     pub fn set_disabled(&mut self, val: bool) {
         self.inner.disabled = val;
     }
@@ -61,6 +38,24 @@ impl CmdTimer {
         self.inner.disabled
     }
 
+    /// Disable the timer outside of this schedule, on top of
+    /// `set_disabled`
+    pub fn set_schedule(&mut self, schedule: Schedule) {
+        self.schedule = schedule;
+    }
+
+    /// Tell the activation command about a logind sleep lock it has
+    /// inherited, through `XIDLEHOOK_SLEEP_LOCK_FD`. Pass `None` once
+    /// it's been activated.
+    pub fn set_sleep_lock(&mut self, fd: Option<RawFd>) {
+        if let Some(ref mut activation) = self.inner.activation {
+            match fd {
+                Some(fd) => activation.env("XIDLEHOOK_SLEEP_LOCK_FD", fd.to_string()),
+                None => activation.env_remove("XIDLEHOOK_SLEEP_LOCK_FD"),
+            };
+        }
+    }
+
     // There's no reason to not have `set_time` here as well, it just
     // isn't available in the API yet.
 
@@ -68,6 +63,17 @@ impl CmdTimer {
         self.inner.time
     }
 
+    /// Returns true if both timers were set up the same way, whether
+    /// or not they're running
+    pub fn same_as(&self, other: &Self) -> bool {
+        self.inner.time == other.inner.time
+            && self.inner.disabled == other.inner.disabled
+            && self.schedule == other.schedule
+            && self.activation == other.activation
+            && self.abortion == other.abortion
+            && self.deactivation == other.deactivation
+    }
+
     pub fn activation(&self) -> &[String] {
         self.activation.as_ref().map_or(&[], |v| &**v)
     }
@@ -123,6 +129,9 @@ impl Timer for CmdTimer {
         self.inner.deactivate()
     }
     fn disabled(&mut self) -> bool {
-        self.inner.disabled()
+        self.inner.disabled() || !self.schedule.active()
+    }
+    fn next_change(&mut self) -> Option<Duration> {
+        self.schedule.next_change()
     }
 }